@@ -1,10 +1,14 @@
 use crate::audio_ctx::AudioCtx;
+use crate::config::CONFIG;
 use crate::video_ctx::VideoCtx;
 use fluorite_common::flume::Sender;
 use fluorite_gba::{
     consts::{HEIGHT, WIDTH},
     gba::Gba,
-    io::keypad::KEYINPUT,
+    io::{
+        keypad::KEYINPUT,
+        sio::{LinkAddr, SocketLink},
+    },
 };
 use sdl2::{event::Event, keyboard::Scancode, EventPump, Sdl};
 
@@ -34,7 +38,21 @@ impl Application {
     pub fn new() -> Self {
         let sdl = sdl2::init().unwrap();
         let (tx, rx) = fluorite_common::flume::bounded(8);
-        let gba = Gba::new(rx);
+        let bios_file = CONFIG.bios_file.display();
+        let bios = match std::fs::read(&CONFIG.bios_file) {
+            Ok(data) => Some(data),
+            Err(e) => {
+                eprintln!("Failed to read BIOS {bios_file}: {e}, using the built-in replacement");
+                None
+            }
+        };
+        let mut gba = Gba::new(bios).unwrap_or_else(|e| {
+            eprintln!("Invalid BIOS {bios_file}: {e}, using the built-in replacement");
+            Gba::new(None).unwrap()
+        });
+        gba.set_key_receiver(rx);
+        gba.set_skip_bios(CONFIG.bios_skip);
+        gba.set_save_interval(CONFIG.save_interval);
         Self {
             video: VideoCtx::init(&sdl),
             audio: AudioCtx::new(&sdl),
@@ -52,16 +70,40 @@ impl Application {
         self.audio.init();
         Gba::load_audio(&mut self.audio);
 
-        if let Some(path) = std::env::args().nth(1) {
+        let mut args = std::env::args().skip(1);
+        if let Some(path) = args.next() {
             self.audio.pause();
             self.gba.load_rom(path);
             self.gba.reset();
+            self.connect_link(args);
             self.state = State::Run;
             self.audio.resume();
             Application::queue_reset();
         }
     }
 
+    /// `host <addr> [players]` or `join <addr>`, `addr` is `ip:port` or `unix:<path>`
+    fn connect_link(&mut self, mut args: impl Iterator<Item = String>) {
+        let role = args.next();
+        let addr: LinkAddr = match args.next() {
+            Some(addr) => addr.parse().unwrap(),
+            None => return,
+        };
+        let link = match role.as_deref() {
+            Some("host") => {
+                let players = args.next().and_then(|n| n.parse().ok()).unwrap_or(2);
+                println!("Waiting for players on {addr:?}");
+                SocketLink::host(&addr, players)
+            }
+            Some("join") => SocketLink::join(&addr),
+            _ => return,
+        };
+        match link {
+            Ok(link) => self.gba.set_link(Box::new(link)),
+            Err(e) => eprintln!("Failed to connect link cable: {e}"),
+        }
+    }
+
     pub fn is_running(&self) -> bool {
         self.state == State::Run || self.state == State::Pause
     }
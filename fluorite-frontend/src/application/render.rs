@@ -28,7 +28,34 @@ impl Application {
                     }
 
                     if ui.menu_item_config("Open save").enabled(running).build() {
-                        todo!("Saves are not supported yet")
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("saves", &["sav"])
+                            .set_directory(&std::env::current_dir().unwrap())
+                            .pick_file()
+                        {
+                            self.audio.pause();
+                            match self.gba.load_save(path) {
+                                Ok(()) => {
+                                    // Games only read their save on boot
+                                    self.gba.reset();
+                                    Application::queue_reset();
+                                }
+                                Err(err) => eprintln!("Failed to load save: {err}"),
+                            }
+                            self.audio.resume();
+                        }
+                    }
+
+                    if ui.menu_item_config("Export save").enabled(running).build() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("saves", &["sav"])
+                            .set_directory(&std::env::current_dir().unwrap())
+                            .save_file()
+                        {
+                            if let Err(err) = self.gba.export_save(path) {
+                                eprintln!("Failed to export save: {err}");
+                            }
+                        }
                     }
 
                     ui.menu_with_enabled("Recent", false, || todo!());
@@ -180,7 +207,10 @@ impl Application {
                     .opened(&mut self.show_registers)
                     .resizable(false)
                     .build(|| {
-                        use fluorite_gba::arm::registers::Reg::*;
+                        use fluorite_gba::arm::{
+                            disasm::{disasm_arm, disasm_thumb},
+                            registers::Reg::*,
+                        };
                         let regs = &self.gba.cpu.regs;
                         ui.text(format!("R0   0x{0:08X?}  {0:10?}", regs.get_reg(R0)));
                         ui.text(format!("R1   0x{0:08X?}  {0:10?}", regs.get_reg(R1)));
@@ -199,6 +229,17 @@ impl Application {
                         ui.text(format!("R14  0x{0:08X?}  {0:10?}", regs.get_reg(R14)));
                         ui.text(format!("R15  0x{0:08X?}  {0:10?}", regs.get_reg(R15)));
                         ui.text(format!("{}", regs.get_status()));
+
+                        ui.separator();
+                        let (pc, bus) = (self.gba.cpu.next_pc(), &self.gba.bus);
+                        let text = if regs.get_t() {
+                            let opcode = bus.debug_read16(pc) as u32
+                                | (bus.debug_read16(pc.wrapping_add(2)) as u32) << 16;
+                            disasm_thumb(pc, opcode)
+                        } else {
+                            disasm_arm(pc, bus.debug_read32(pc))
+                        };
+                        ui.text(format!("0x{pc:08X}  {text}"));
                     });
             }
         });
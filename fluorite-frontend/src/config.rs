@@ -8,6 +8,7 @@ pub struct Config {
     pub bios_skip: bool,
     pub fast_forward: u32,
     pub frame_size: u32,
+    pub save_interval: usize,
     pub volume: Cell<f32>,
     pub mute: Cell<bool>,
 }
@@ -20,6 +21,7 @@ impl Config {
             bios_skip: true,
             fast_forward: 1000000,
             frame_size: 4,
+            save_interval: 60,
             volume: Cell::new(0.5),
             mute: Cell::new(true),
         }
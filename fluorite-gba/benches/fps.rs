@@ -1,5 +1,4 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use fluorite_common::flume::unbounded;
 use fluorite_gba::{gba::Gba, AudioInterface};
 
 struct DummyAudio;
@@ -16,14 +15,17 @@ fn run_gba(gba: &mut Gba) {
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
-    let (_, rx) = unbounded();
-    let mut gba = Gba::new(rx);
+    let mut gba = Gba::new(None).unwrap();
     let mut dummy = DummyAudio;
     Gba::load_audio(&mut dummy);
-    gba.load_rom("C:\\Users\\johnf\\CLionProjects\\fluorite\\roms\\pokemon\\Pokemon Emerald.gba");
+    // Any rom can be benchmarked through FLUORITE_BENCH_ROM
+    let rom = std::env::var("FLUORITE_BENCH_ROM").unwrap_or_else(|_| {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../roms/tonc/m7_demo.gba").into()
+    });
+    gba.load_rom(&rom);
     gba.reset();
 
-    c.bench_function("fps pokemon", |b| b.iter(|| run_gba(&mut gba)));
+    c.bench_function("fps", |b| b.iter(|| run_gba(&mut gba)));
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -96,6 +96,8 @@ fn generate_arm_lut<P: AsRef<Path>>(path: P) -> io::Result<()> {
             )
         } else if inst & 0xF800FF0 == 0x1000090 {
             format!("single_data_swap::<{}>", bit!(inst, 22))
+        } else if inst & 0xE0000F0 == 0x90 {
+            "undefined_instr_arm".to_string()
         } else if inst & 0xE000090 == 0x90 {
             format!(
                 "halfword_and_signed_data_transfer::<{}, {}, {}, {}, {}, {} ,{}>",
@@ -144,6 +146,8 @@ fn generate_arm_lut<P: AsRef<Path>>(path: P) -> io::Result<()> {
                 (field4 >> 1) & 3,
                 !field4 & 1 != 0
             )
+        } else if inst & 0xE000010 == 0x6000010 {
+            "undefined_instr_arm".to_string()
         } else if inst & 0xC000000 == 0x4000000 {
             format!(
                 "single_data_transfer::<{}, {}, {}, {}, {}, {}>",
@@ -172,11 +176,7 @@ fn generate_arm_lut<P: AsRef<Path>>(path: P) -> io::Result<()> {
         } else if inst & 0xF000000 == 0xE000000 {
             "coprocessor".to_string()
         } else {
-            assert_eq!(
-                inst & 0b1110_0000_0000_0000_0000_0001_0000,
-                0b1110_0000_0000_0000_0000_0001_0000
-            );
-            "undefined_instr_arm".to_string()
+            unreachable!()
         };
 
         writeln!(file, "\tArm7tdmi::{},", output)?;
@@ -245,6 +245,8 @@ fn generate_thumb_lut<P: AsRef<Path>>(path: P) -> io::Result<()> {
             )
         } else if opcode & 0b1111_1111 == 0b1101_1111 {
             "thumb_software_interrupt".to_string()
+        } else if opcode & 0b1111_1111 == 0b1101_1110 {
+            "undefined_instr_thumb".to_string()
         } else if opcode & 0b1111_0000 == 0b1101_0000 {
             format!("cond_branch::<{}>", bits!(inst, 8, 11),)
         } else if opcode & 0b1111_1000 == 0b1110_0000 {
@@ -12,60 +12,17 @@ include!(concat!(env!("OUT_DIR"), "/arm_lut.rs"));
 impl Arm7tdmi {
     pub(super) fn fill_arm_instr_buffer(&mut self, bus: &mut Sysbus) {
         self.regs.pc &= !0x3;
-        self.pipeline[0] = self.read::<u32>(bus, MemoryAccess::S, self.regs.pc & !0x3);
+        self.pipeline[0] = self.fetch::<u32>(bus, MemoryAccess::S, self.regs.pc & !0x3);
         self.regs.pc = self.regs.pc.wrapping_add(4);
 
-        self.pipeline[1] = self.read::<u32>(bus, MemoryAccess::S, self.regs.pc & !0x3);
-    }
-
-    #[inline]
-    #[cfg(feature = "decode")]
-    pub fn decode_arm_instr(addr: u32, instr: u32) -> String {
-        use yaxpeax_arch::{Arch, Decoder, U8Reader};
-        use yaxpeax_arm::armv7::ARMv7;
-
-        let data = [
-            (instr & 0xFF) as u8,
-            (instr >> 8 & 0xFF) as u8,
-            (instr >> 16 & 0xFF) as u8,
-            (instr >> 24 & 0xFF) as u8,
-        ];
-
-        let mut reader = U8Reader::new(&data);
-        let decoder = <ARMv7 as Arch>::Decoder::default();
-
-        match decoder.decode(&mut reader) {
-            Ok(i) => format!("{:08X?} [{:08X?}] -> {i}", addr, instr,),
-            Err(e) => format!("{e:?}"),
-        }
+        self.pipeline[1] = self.fetch::<u32>(bus, MemoryAccess::S, self.regs.pc & !0x3);
     }
 
     pub(super) fn emulate_arm_instr(&mut self, bus: &mut Sysbus) {
         let instr = self.pipeline[0];
 
-        #[cfg(feature = "decode")]
-        {
-            use std::io::Write;
-
-            let reg = &self.regs;
-
-            writeln!(
-                self.decode_log,
-                "{:<50}  ({:08X} {:08X} {:08X} {:08X} {:08X}) {}",
-                Self::decode_arm_instr(self.regs.pc.wrapping_sub(4), instr),
-                reg.get_reg_i(0),
-                reg.get_reg_i(1),
-                reg.get_reg_i(2),
-                reg.get_reg_i(3),
-                reg.get_reg_i(11),
-                format!(
-                    "N: {} Z: {} C: {} V {}",
-                    reg.get_n() as u8,
-                    reg.get_z() as u8,
-                    reg.get_c() as u8,
-                    reg.get_v() as u8
-                )
-            );
+        if self.tracer.is_some() {
+            self.trace(instr);
         }
 
         self.pipeline[0] = self.pipeline[1];
@@ -658,9 +615,12 @@ impl Arm7tdmi {
     }
 
     // ARM.13: Software Interrupt (SWI)
-    fn arm_software_interrupt(&mut self, bus: &mut Sysbus, _instr: u32) {
+    fn arm_software_interrupt(&mut self, bus: &mut Sysbus, instr: u32) {
         // assert_eq!(instr >> 24 & 0xF, 0b1111);
         self.instruction_prefetch::<u32>(bus, MemoryAccess::N);
+        if bus.is_hle_bios() {
+            return self.hle_software_interrupt(bus, (instr >> 16 & 0xFF) as u8);
+        }
         self.regs.change_mode(Mode::Supervisor);
         self.regs.set_reg(Reg::R14, self.regs.pc.wrapping_sub(4));
         self.regs.set_i(true);
@@ -671,13 +631,12 @@ impl Arm7tdmi {
     // ARM.14: Coprocessor Data Operations (CDP)
     // ARM.15: Coprocessor Data Transfers (LDC,STC)
     // ARM.16: Coprocessor Register Transfers (MRC, MCR)
-    fn coprocessor(&mut self, _bus: &mut Sysbus, _instr: u32) {
-        unimplemented!("Coprocessor not implemented!");
+    fn coprocessor(&mut self, bus: &mut Sysbus, instr: u32) {
+        self.undefined_exception(bus, instr);
     }
 
     // ARM.17: Undefined Instruction
-    #[allow(dead_code)]
-    fn undefined_instr_arm(&mut self, _: &mut Sysbus, _: u32) {
-        unimplemented!("ARM.17: Undefined Instruction not implemented!");
+    fn undefined_instr_arm(&mut self, bus: &mut Sysbus, instr: u32) {
+        self.undefined_exception(bus, instr);
     }
 }
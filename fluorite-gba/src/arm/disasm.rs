@@ -0,0 +1,477 @@
+//! Turns ARM and Thumb opcodes into UAL style assembly. Encodings are split the same way
+//! as the instruction LUTs in build.rs, so the text matches what the interpreter executes.
+//! Branch targets and the addresses of PC-relative loads are resolved with the address of
+//! the instruction.
+
+use std::fmt::Write;
+
+const CONDITIONS: [&str; 16] = [
+    "eq", "ne", "cs", "cc", "mi", "pl", "vs", "vc", "hi", "ls", "ge", "lt", "gt", "le", "", "nv",
+];
+const SHIFTS: [&str; 4] = ["lsl", "lsr", "asr", "ror"];
+
+/// Disassembles the ARM instruction `opcode` located at `addr`
+pub fn disasm_arm(addr: u32, opcode: u32) -> String {
+    let cond = CONDITIONS[(opcode >> 28) as usize];
+    let rn = reg(opcode >> 16);
+    let rd = reg(opcode >> 12);
+    let rs = reg(opcode >> 8);
+    let rm = reg(opcode);
+    let s = if bit(opcode, 20) { "s" } else { "" };
+
+    // Only the bits the LUT is indexed with
+    let inst = opcode & 0x0FF0_00F0;
+    if inst & 0xFF000F0 == 0x1200010 {
+        // ARM.3: Branch and Exchange
+        format!("bx{cond} {rm}")
+    } else if inst & 0xFC000F0 == 0x90 {
+        // ARM.7: Multiply and Multiply-Accumulate
+        if bit(opcode, 21) {
+            format!("mla{s}{cond} {rn}, {rm}, {rs}, {rd}")
+        } else {
+            format!("mul{s}{cond} {rn}, {rm}, {rs}")
+        }
+    } else if inst & 0xF8000F0 == 0x800090 {
+        let sign = if bit(opcode, 22) { "s" } else { "u" };
+        let op = if bit(opcode, 21) { "mlal" } else { "mull" };
+        format!("{sign}{op}{s}{cond} {rd}, {rn}, {rm}, {rs}")
+    } else if inst & 0xF800FF0 == 0x1000090 {
+        // ARM.12: Single Data Swap
+        let b = if bit(opcode, 22) { "b" } else { "" };
+        format!("swp{b}{cond} {rd}, {rm}, [{rn}]")
+    } else if inst & 0xE0000F0 == 0x90 {
+        // Neither a multiply nor a swap, with no halfword transfer either
+        "undefined".into()
+    } else if inst & 0xE000090 == 0x90 {
+        // ARM.10: Halfword and Signed Data Transfer
+        let op = if bit(opcode, 20) { "ldr" } else { "str" };
+        let size = match opcode >> 5 & 0x3 {
+            1 => "h",
+            2 => "sb",
+            _ => "sh",
+        };
+        let (offset, literal) = if bit(opcode, 22) {
+            let imm = (opcode >> 4 & 0xF0) | (opcode & 0xF);
+            (Offset::Imm(imm), literal(addr, opcode, imm))
+        } else {
+            (Offset::Reg(opcode & 0xF), String::new())
+        };
+        let address = address(opcode, offset);
+        format!("{op}{size}{cond} {rd}, {address}{literal}")
+    } else if inst & 0xD900000 == 0x1000000 {
+        // ARM.6: PSR Transfer
+        let psr = if bit(opcode, 22) { "spsr" } else { "cpsr" };
+        if !bit(opcode, 21) {
+            return format!("mrs{cond} {rd}, {psr}");
+        }
+        let fields: String = [(19, 'f'), (18, 's'), (17, 'x'), (16, 'c')]
+            .into_iter()
+            .filter(|&(i, _)| bit(opcode, i))
+            .map(|(_, field)| field)
+            .collect();
+        let source = if bit(opcode, 25) {
+            format!("#{}", hex(rotated_imm(opcode)))
+        } else {
+            rm.to_string()
+        };
+        format!("msr{cond} {psr}_{fields}, {source}")
+    } else if inst & 0xC000000 == 0x0 {
+        // ARM.5: Data Processing
+        let op2 = if bit(opcode, 25) {
+            format!("#{}", hex(rotated_imm(opcode)))
+        } else {
+            shifted_reg(opcode)
+        };
+        match opcode >> 21 & 0xF {
+            op @ (0x2 | 0x4) if rn == "pc" && s.is_empty() && bit(opcode, 25) => {
+                let base = addr.wrapping_add(8);
+                let target = if op == 0x2 {
+                    base.wrapping_sub(rotated_imm(opcode))
+                } else {
+                    base.wrapping_add(rotated_imm(opcode))
+                };
+                format!("adr{cond} {rd}, {target:#010x}")
+            }
+            op @ 0x8..=0xB => {
+                let op = ["tst", "teq", "cmp", "cmn"][op as usize - 0x8];
+                format!("{op}{cond} {rn}, {op2}")
+            }
+            op @ (0xD | 0xF) => {
+                let op = if op == 0xD { "mov" } else { "mvn" };
+                format!("{op}{s}{cond} {rd}, {op2}")
+            }
+            op => {
+                let op = [
+                    "and", "eor", "sub", "rsb", "add", "adc", "sbc", "rsc", "", "", "", "", "orr",
+                    "", "bic",
+                ][op as usize];
+                format!("{op}{s}{cond} {rd}, {rn}, {op2}")
+            }
+        }
+    } else if inst & 0xC000000 == 0x4000000 {
+        // ARM.9: Single Data Transfer
+        if bit(opcode, 25) && bit(opcode, 4) {
+            return "undefined".into();
+        }
+        let op = if bit(opcode, 20) { "ldr" } else { "str" };
+        let b = if bit(opcode, 22) { "b" } else { "" };
+        let t = if !bit(opcode, 24) && bit(opcode, 21) {
+            "t"
+        } else {
+            ""
+        };
+        let (offset, literal) = if bit(opcode, 25) {
+            let offset = Offset::Shifted(opcode & 0xF, opcode >> 5 & 0x3, opcode >> 7 & 0x1F);
+            (offset, String::new())
+        } else {
+            let imm = opcode & 0xFFF;
+            (Offset::Imm(imm), literal(addr, opcode, imm))
+        };
+        let address = address(opcode, offset);
+        format!("{op}{b}{t}{cond} {rd}, {address}{literal}")
+    } else if inst & 0xE000000 == 0x8000000 {
+        // ARM.11: Block Data Transfer
+        let load = bit(opcode, 20);
+        let writeback = bit(opcode, 21);
+        let user = bit(opcode, 22);
+        let list = reg_list(opcode & 0xFFFF);
+        let mode = match (bit(opcode, 24), bit(opcode, 23)) {
+            (false, false) => "da",
+            (false, true) => "",
+            (true, false) => "db",
+            (true, true) => "ib",
+        };
+        match (load, mode) {
+            (true, "") | (false, "db") if opcode >> 16 & 0xF == 13 && writeback && !user => {
+                let op = if load { "pop" } else { "push" };
+                format!("{op}{cond} {list}")
+            }
+            _ => {
+                let op = if load { "ldm" } else { "stm" };
+                let writeback = if writeback { "!" } else { "" };
+                let user = if user { "^" } else { "" };
+                format!("{op}{mode}{cond} {rn}{writeback}, {list}{user}")
+            }
+        }
+    } else if inst & 0xE000000 == 0xA000000 {
+        // ARM.4: Branch and Branch with Link
+        let l = if bit(opcode, 24) { "l" } else { "" };
+        let offset = ((opcode << 8) as i32 >> 6) as u32;
+        let target = addr.wrapping_add(8).wrapping_add(offset);
+        format!("b{l}{cond} {target:#010x}")
+    } else if inst & 0xF000000 == 0xF000000 {
+        // ARM.13: Software Interrupt
+        format!("svc{cond} #{}", hex(opcode & 0xFF_FFFF))
+    } else if inst & 0xE000000 == 0xC000000 || inst & 0xF000000 == 0xE000000 {
+        // ARM.14-16: Coprocessor instructions
+        disasm_coprocessor(opcode)
+    } else {
+        // ARM.17: Undefined Instruction
+        "undefined".into()
+    }
+}
+
+fn disasm_coprocessor(opcode: u32) -> String {
+    let cond = CONDITIONS[(opcode >> 28) as usize];
+    let cp = opcode >> 8 & 0xF;
+    let crn = opcode >> 16 & 0xF;
+    let crd = opcode >> 12 & 0xF;
+    let crm = opcode & 0xF;
+    let op2 = opcode >> 5 & 0x7;
+    if opcode & 0x0E00_0000 == 0x0C00_0000 {
+        let op = if bit(opcode, 20) { "ldc" } else { "stc" };
+        let l = if bit(opcode, 22) { "l" } else { "" };
+        let address = address(opcode, Offset::Imm((opcode & 0xFF) << 2));
+        format!("{op}{l}{cond} p{cp}, c{crd}, {address}")
+    } else if bit(opcode, 4) {
+        let op = if bit(opcode, 20) { "mrc" } else { "mcr" };
+        let op1 = opcode >> 21 & 0x7;
+        let rd = reg(opcode >> 12);
+        format!("{op}{cond} p{cp}, #{op1}, {rd}, c{crn}, c{crm}, #{op2}")
+    } else {
+        let op1 = opcode >> 20 & 0xF;
+        format!("cdp{cond} p{cp}, #{op1}, c{crd}, c{crn}, c{crm}, #{op2}")
+    }
+}
+
+/// Disassembles the Thumb instruction in the lower half of `opcode` located at `addr`.
+/// The upper half is the next halfword, which completes the first half of a BL.
+pub fn disasm_thumb(addr: u32, opcode: u32) -> String {
+    let next = (opcode >> 16) as u16;
+    let opcode = opcode as u16;
+    let op = |i: u16, len: u16| opcode >> i & ((1 << len) - 1);
+    let lo = |i: u16| reg(op(i, 3) as u32);
+    let rd = lo(0);
+    let rs = lo(3);
+
+    match opcode >> 8 {
+        // THUMB.2: add/subtract
+        key if key & 0b1111_1000 == 0b0001_1000 => {
+            let name = if op(9, 1) != 0 { "subs" } else { "adds" };
+            let operand = if op(10, 1) != 0 {
+                format!("#{}", op(6, 3))
+            } else {
+                lo(6).to_string()
+            };
+            format!("{name} {rd}, {rs}, {operand}")
+        }
+        // THUMB.1: move shifted register
+        key if key & 0b1110_0000 == 0b0000_0000 => match (op(11, 2), op(6, 5)) {
+            (0, 0) => format!("movs {rd}, {rs}"),
+            (shift, amount) => {
+                let amount = if amount == 0 { 32 } else { amount };
+                format!("{}s {rd}, {rs}, #{amount}", SHIFTS[shift as usize])
+            }
+        },
+        // THUMB.3: move/compare/add/subtract immediate
+        key if key & 0b1110_0000 == 0b0010_0000 => {
+            let name = ["movs", "cmp", "adds", "subs"][op(11, 2) as usize];
+            format!("{name} {}, #{}", lo(8), hex(op(0, 8) as u32))
+        }
+        // THUMB.4: ALU operations
+        key if key & 0b1111_1100 == 0b0100_0000 => match op(6, 4) {
+            0x9 => format!("negs {rd}, {rs}"),
+            0xD => format!("muls {rd}, {rs}, {rd}"),
+            alu => {
+                let name = [
+                    "ands", "eors", "lsls", "lsrs", "asrs", "adcs", "sbcs", "rors", "tst", "",
+                    "cmp", "cmn", "orrs", "", "bics", "mvns",
+                ][alu as usize];
+                format!("{name} {rd}, {rs}")
+            }
+        },
+        // THUMB.5: Hi register operations/branch exchange
+        key if key & 0b1111_1100 == 0b0100_0100 => {
+            let rd = reg((op(7, 1) << 3 | op(0, 3)) as u32);
+            let rs = reg(op(3, 4) as u32);
+            match op(8, 2) {
+                0 => format!("add {rd}, {rs}"),
+                1 => format!("cmp {rd}, {rs}"),
+                2 => format!("mov {rd}, {rs}"),
+                _ => format!("bx {rs}"),
+            }
+        }
+        // THUMB.6: load PC-relative
+        key if key & 0b1111_1000 == 0b0100_1000 => {
+            let offset = op(0, 8) as u32 * 4;
+            let target = (addr.wrapping_add(4) & !0x3).wrapping_add(offset);
+            format!("ldr {}, [pc, #{}] ; {target:#010x}", lo(8), hex(offset))
+        }
+        // THUMB.7: load/store with register offset
+        key if key & 0b1111_0010 == 0b0101_0000 => {
+            let name = ["str", "strb", "ldr", "ldrb"][op(10, 2) as usize];
+            format!("{name} {rd}, [{rs}, {}]", lo(6))
+        }
+        // THUMB.8: load/store sign-extended byte/halfword
+        key if key & 0b1111_0010 == 0b0101_0010 => {
+            let name = ["strh", "ldrsb", "ldrh", "ldrsh"][op(10, 2) as usize];
+            format!("{name} {rd}, [{rs}, {}]", lo(6))
+        }
+        // THUMB.9: load/store with immediate offset
+        key if key & 0b1110_0000 == 0b0110_0000 => {
+            let (name, scale) = match op(11, 2) {
+                0 => ("str", 4),
+                1 => ("ldr", 4),
+                2 => ("strb", 1),
+                _ => ("ldrb", 1),
+            };
+            format!("{name} {rd}, [{rs}{}]", imm_offset(op(6, 5) as u32 * scale))
+        }
+        // THUMB.10: load/store halfword
+        key if key & 0b1111_0000 == 0b1000_0000 => {
+            let name = if op(11, 1) != 0 { "ldrh" } else { "strh" };
+            format!("{name} {rd}, [{rs}{}]", imm_offset(op(6, 5) as u32 * 2))
+        }
+        // THUMB.11: load/store SP-relative
+        key if key & 0b1111_0000 == 0b1001_0000 => {
+            let name = if op(11, 1) != 0 { "ldr" } else { "str" };
+            let offset = imm_offset(op(0, 8) as u32 * 4);
+            format!("{name} {}, [sp{offset}]", lo(8))
+        }
+        // THUMB.12: get relative address
+        key if key & 0b1111_0000 == 0b1010_0000 => {
+            let offset = op(0, 8) as u32 * 4;
+            if op(11, 1) != 0 {
+                format!("add {}, sp, #{}", lo(8), hex(offset))
+            } else {
+                let target = (addr.wrapping_add(4) & !0x3).wrapping_add(offset);
+                format!("adr {}, {target:#010x}", lo(8))
+            }
+        }
+        // THUMB.13: add offset to stack pointer
+        0b1011_0000 => {
+            let name = if op(7, 1) != 0 { "sub" } else { "add" };
+            format!("{name} sp, #{}", hex(op(0, 7) as u32 * 4))
+        }
+        // THUMB.14: push/pop registers
+        key if key & 0b1111_0110 == 0b1011_0100 => {
+            let (name, extra) = if op(11, 1) != 0 {
+                ("pop", 1 << 15)
+            } else {
+                ("push", 1 << 14)
+            };
+            let list = op(0, 8) as u32 | if op(8, 1) != 0 { extra } else { 0 };
+            format!("{name} {}", reg_list(list))
+        }
+        // THUMB.15: multiple load/store
+        key if key & 0b1111_0000 == 0b1100_0000 => {
+            let name = if op(11, 1) != 0 { "ldmia" } else { "stmia" };
+            format!("{name} {}!, {}", lo(8), reg_list(op(0, 8) as u32))
+        }
+        // THUMB.17: software interrupt
+        0b1101_1111 => format!("svc #{}", hex(op(0, 8) as u32)),
+        // THUMB.16: conditional branch
+        key if key & 0b1111_0000 == 0b1101_0000 => {
+            if op(8, 4) == 0xE {
+                return "undefined".into();
+            }
+            let offset = (op(0, 8) as i8 as i32 * 2) as u32;
+            let target = addr.wrapping_add(4).wrapping_add(offset);
+            format!("b{} {target:#010x}", CONDITIONS[op(8, 4) as usize])
+        }
+        // THUMB.18: unconditional branch
+        key if key & 0b1111_1000 == 0b1110_0000 => {
+            let offset = (((op(0, 11) << 5) as i16 >> 4) as i32) as u32;
+            format!("b {:#010x}", addr.wrapping_add(4).wrapping_add(offset))
+        }
+        // THUMB.19: long branch with link
+        key if key & 0b1111_0000 == 0b1111_0000 => {
+            let high = (((op(0, 11) as u32) << 21) as i32 >> 9) as u32;
+            if op(11, 1) != 0 {
+                format!("bl lr, #{}", hex(op(0, 11) as u32 * 2))
+            } else if next & 0xF800 == 0xF800 {
+                let low = (next & 0x7FF) as u32 * 2;
+                let target = addr.wrapping_add(4).wrapping_add(high).wrapping_add(low);
+                format!("bl {target:#010x}")
+            } else if (high as i32) < 0 {
+                format!("sub lr, pc, #{}", hex(high.wrapping_neg()))
+            } else {
+                format!("add lr, pc, #{}", hex(high))
+            }
+        }
+        _ => "undefined".into(),
+    }
+}
+
+enum Offset {
+    Imm(u32),
+    Reg(u32),
+    // A register with shift type and amount
+    Shifted(u32, u32, u32),
+}
+
+/// The addressing mode of ARM.9, ARM.10 and ARM.14
+fn address(opcode: u32, offset: Offset) -> String {
+    let rn = reg(opcode >> 16);
+    let sign = if bit(opcode, 23) { "" } else { "-" };
+    let offset = match offset {
+        Offset::Imm(0) if bit(opcode, 23) => String::new(),
+        Offset::Imm(imm) => format!(", #{sign}{}", hex(imm)),
+        Offset::Reg(rm) => format!(", {sign}{}", reg(rm)),
+        Offset::Shifted(rm, shift_type, amount) => {
+            format!(", {sign}{}{}", reg(rm), shift_suffix(shift_type, amount))
+        }
+    };
+    if !bit(opcode, 24) {
+        format!("[{rn}]{offset}")
+    } else if bit(opcode, 21) {
+        format!("[{rn}{offset}]!")
+    } else {
+        format!("[{rn}{offset}]")
+    }
+}
+
+/// The address an immediate offset from PC points to, as a comment after the instruction
+fn literal(addr: u32, opcode: u32, offset: u32) -> String {
+    // Only pre-indexed without writeback, PC can't be written back to
+    if opcode >> 16 & 0xF != 15 || !bit(opcode, 24) || bit(opcode, 21) {
+        return String::new();
+    }
+    let base = addr.wrapping_add(8);
+    let target = if bit(opcode, 23) {
+        base.wrapping_add(offset)
+    } else {
+        base.wrapping_sub(offset)
+    };
+    format!(" ; {target:#010x}")
+}
+
+/// The second operand of ARM.5 with a register
+fn shifted_reg(opcode: u32) -> String {
+    let rm = reg(opcode);
+    let shift_type = opcode >> 5 & 0x3;
+    if bit(opcode, 4) {
+        format!("{rm}, {} {}", SHIFTS[shift_type as usize], reg(opcode >> 8))
+    } else {
+        format!("{rm}{}", shift_suffix(shift_type, opcode >> 7 & 0x1F))
+    }
+}
+
+/// Immediate shifts, where an amount of 0 encodes LSR/ASR #32 and RRX
+fn shift_suffix(shift_type: u32, amount: u32) -> String {
+    match (shift_type, amount) {
+        (0, 0) => String::new(),
+        (3, 0) => ", rrx".into(),
+        (_, 0) => format!(", {} #32", SHIFTS[shift_type as usize]),
+        _ => format!(", {} #{amount}", SHIFTS[shift_type as usize]),
+    }
+}
+
+fn rotated_imm(opcode: u32) -> u32 {
+    (opcode & 0xFF).rotate_right((opcode >> 8 & 0xF) * 2)
+}
+
+fn imm_offset(offset: u32) -> String {
+    if offset == 0 {
+        String::new()
+    } else {
+        format!(", #{}", hex(offset))
+    }
+}
+
+/// `{r0-r3, lr}`
+fn reg_list(list: u32) -> String {
+    let mut text = String::from("{");
+    let mut i = 0;
+    while i < 16 {
+        if list & 1 << i == 0 {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < 16 && list & 1 << i != 0 {
+            i += 1;
+        }
+        if text.len() > 1 {
+            text.push_str(", ");
+        }
+        match i - start {
+            1 => write!(text, "{}", reg(start)),
+            2 => write!(text, "{}, {}", reg(start), reg(start + 1)),
+            _ => write!(text, "{}-{}", reg(start), reg(i - 1)),
+        }
+        .unwrap();
+    }
+    text.push('}');
+    text
+}
+
+fn reg(reg: u32) -> &'static str {
+    [
+        "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11", "r12", "sp",
+        "lr", "pc",
+    ][(reg & 0xF) as usize]
+}
+
+/// Small numbers are more readable in decimal
+fn hex(value: u32) -> String {
+    if value < 10 {
+        value.to_string()
+    } else {
+        format!("{value:#x}")
+    }
+}
+
+fn bit(opcode: u32, i: u32) -> bool {
+    opcode >> i & 0x1 != 0
+}
@@ -0,0 +1,490 @@
+use super::{registers::Registers, Arm7tdmi};
+use crate::io::Sysbus;
+use std::f64::consts::TAU;
+
+/// Mapped at 0 when there is no BIOS dump. It only holds the exception vectors and an IRQ
+/// handler made of the same instructions, at the same addresses, as the real one, so open
+/// bus reads of the BIOS after an interrupt see the values games expect.
+pub(crate) static STUB_BIOS: [u8; 0x4000] = stub_bios();
+
+const fn stub_bios() -> [u8; 0x4000] {
+    const CODE: [(usize, u32); 14] = [
+        (0x00, 0xEAFF_FFFE),  // Reset: b 0x00
+        (0x04, 0xE1B0_F00E),  // Undefined: movs pc, lr
+        (0x08, 0xE1B0_F00E),  // SWI: movs pc, lr
+        (0x0C, 0xE25E_F004),  // Prefetch abort: subs pc, lr, #4
+        (0x10, 0xE25E_F008),  // Data abort: subs pc, lr, #8
+        (0x14, 0xEAFF_FFFE),  // Reserved: b 0x14
+        (0x18, 0xEA00_0042),  // IRQ: b 0x128
+        (0x1C, 0xE25E_F004),  // FIQ: subs pc, lr, #4
+        (0x128, 0xE92D_500F), // stmfd sp!, {r0-r3, r12, lr}
+        (0x12C, 0xE3A0_0301), // mov r0, #0x04000000
+        (0x130, 0xE28F_E000), // add lr, pc, #0
+        (0x134, 0xE510_F004), // ldr pc, [r0, #-4]
+        (0x138, 0xE8BD_500F), // ldmfd sp!, {r0-r3, r12, lr}
+        (0x13C, 0xE25E_F004), // subs pc, lr, #4
+    ];
+
+    let mut bios = [0; 0x4000];
+    let mut i = 0;
+    while i < CODE.len() {
+        let (addr, instr) = CODE[i];
+        bios[addr] = instr as u8;
+        bios[addr + 1] = (instr >> 8) as u8;
+        bios[addr + 2] = (instr >> 16) as u8;
+        bios[addr + 3] = (instr >> 24) as u8;
+        i += 1;
+    }
+    bios
+}
+
+// Interrupt flags the game's IRQ handler sets for IntrWait
+const BIOS_IF: u32 = 0x0300_7FF8;
+const SOFT_RESET_FLAG: u32 = 0x0300_7FFA;
+
+// What the real BIOS leaves on the bus when returning from a SWI
+const SWI_RETURN_LATCH: u32 = 0xE3A0_2004;
+
+impl Arm7tdmi {
+    /// Runs the BIOS function `comment` natively instead of entering the SWI vector
+    pub(super) fn hle_software_interrupt(&mut self, bus: &mut Sysbus, comment: u8) {
+        let r = |i: u32| self.regs.get_reg_i(i);
+        let (r0, r1, r2, r3) = (r(0), r(1), r(2), r(3));
+        match comment {
+            0x00 => return self.soft_reset(bus),
+            0x01 => Self::register_ram_reset(bus, r0),
+            0x02 => bus.write::<u8>(0x0400_0301, 0x00),
+            0x03 => bus.write::<u8>(0x0400_0301, 0x80),
+            0x04 => return self.intr_wait(bus, r0 != 0, r1 as u16),
+            0x05 => return self.intr_wait(bus, true, 0x1),
+            0x06 => self.div(r0 as i32, r1 as i32),
+            0x07 => self.div(r1 as i32, r0 as i32),
+            0x08 => self.regs.set_reg_i(0, ((r0 as f64).sqrt() as u32) & 0xFFFF),
+            0x09 => {
+                let (result, a, b) = arctan(r0 as i32);
+                self.regs.set_reg_i(0, result as u32);
+                self.regs.set_reg_i(1, a as u32);
+                self.regs.set_reg_i(3, b as u32);
+            }
+            0x0A => {
+                let result = arctan2(r0 as i32, r1 as i32);
+                self.regs.set_reg_i(0, result as u32);
+                self.regs.set_reg_i(3, 0x170);
+            }
+            0x0B => Self::cpu_set(bus, r0, r1, r2),
+            0x0C => Self::cpu_fast_set(bus, r0, r1, r2),
+            0x0D => self.regs.set_reg_i(0, 0xBAAE_187F),
+            0x0E => Self::bg_affine_set(bus, r0, r1, r2),
+            0x0F => Self::obj_affine_set(bus, r0, r1, r2, r3),
+            0x10 => Self::bit_unpack(bus, r0, r1, r2),
+            0x11 => Self::write_bytes(bus, r1, &lz77_decompress(bus, r0)),
+            0x12 => Self::write_halfwords(bus, r1, &lz77_decompress(bus, r0)),
+            0x13 => Self::huffman_decompress(bus, r0, r1),
+            0x14 => Self::write_bytes(bus, r1, &rl_decompress(bus, r0)),
+            0x15 => Self::write_halfwords(bus, r1, &rl_decompress(bus, r0)),
+            0x16 => Self::write_bytes(bus, r1, &diff_unfilter(bus, r0)),
+            0x17 | 0x18 => Self::write_halfwords(bus, r1, &diff_unfilter(bus, r0)),
+            _ => warn!("Unimplemented HLE SWI 0x{comment:02X}"),
+        }
+        self.return_from_swi(bus, false);
+    }
+
+    // Continues after the SWI, or runs it again when `repeat` is set
+    fn return_from_swi(&mut self, bus: &mut Sysbus, repeat: bool) {
+        let len = if self.regs.get_t() { 2 } else { 4 };
+        let instrs = if repeat { 2 } else { 1 };
+        self.regs.pc = self.regs.pc.wrapping_sub(len * instrs);
+        if self.regs.get_t() {
+            self.fill_thumb_instr_buffer(bus);
+        } else {
+            self.fill_arm_instr_buffer(bus);
+        }
+        bus.set_bios_latch(SWI_RETURN_LATCH);
+    }
+
+    fn soft_reset(&mut self, bus: &mut Sysbus) {
+        let to_ewram = bus.read::<u8>(SOFT_RESET_FLAG) != 0;
+        for addr in (0x0300_7E00..0x0300_8000).step_by(4) {
+            bus.write::<u32>(addr, 0);
+        }
+        self.regs = Registers::new();
+        self.regs.skip_bios();
+        self.regs.pc = if to_ewram { 0x0200_0000 } else { 0x0800_0000 };
+        self.intr_waiting = false;
+        self.fill_arm_instr_buffer(bus);
+        bus.set_bios_latch(SWI_RETURN_LATCH);
+    }
+
+    fn register_ram_reset(bus: &mut Sysbus, flags: u32) {
+        let clear = |bus: &mut Sysbus, start: u32, len: u32| {
+            for addr in (start..start + len).step_by(4) {
+                bus.write::<u32>(addr, 0);
+            }
+        };
+
+        bus.write::<u16>(0x0400_0000, 0x80);
+        if flags & 0x01 != 0 {
+            clear(bus, 0x0200_0000, 0x40000);
+        }
+        // The top of IWRAM holds the stacks and the IRQ handler address
+        if flags & 0x02 != 0 {
+            clear(bus, 0x0300_0000, 0x7E00);
+        }
+        if flags & 0x04 != 0 {
+            clear(bus, 0x0500_0000, 0x400);
+        }
+        if flags & 0x08 != 0 {
+            clear(bus, 0x0600_0000, 0x18000);
+        }
+        if flags & 0x10 != 0 {
+            clear(bus, 0x0700_0000, 0x400);
+        }
+        if flags & 0x20 != 0 {
+            clear(bus, 0x0400_0120, 0x10);
+            bus.write::<u16>(0x0400_0134, 0x8000);
+            clear(bus, 0x0400_0140, 0x4);
+            clear(bus, 0x0400_0150, 0x8);
+        }
+        if flags & 0x40 != 0 {
+            clear(bus, 0x0400_0060, 0x48);
+        }
+        if flags & 0x80 != 0 {
+            clear(bus, 0x0400_0004, 0x5C);
+            // The affine backgrounds go back to an identity matrix
+            for addr in [0x0400_0020, 0x0400_0026, 0x0400_0030, 0x0400_0036] {
+                bus.write::<u16>(addr, 0x100);
+            }
+            clear(bus, 0x0400_00B0, 0x30);
+            clear(bus, 0x0400_0100, 0x10);
+            bus.write::<u16>(0x0400_0132, 0);
+            bus.write::<u16>(0x0400_0200, 0);
+            bus.write::<u16>(0x0400_0202, 0xFFFF);
+            bus.write::<u16>(0x0400_0204, 0);
+            bus.write::<u16>(0x0400_0208, 0);
+        }
+    }
+
+    // The real BIOS halts until the IRQ handler sets one of the waited for flags. Here the
+    // SWI halts and runs again after every interrupt, `intr_waiting` remembers that old flags
+    // were already discarded.
+    fn intr_wait(&mut self, bus: &mut Sysbus, discard: bool, mask: u16) {
+        bus.write::<u16>(0x0400_0208, 1);
+        let flags = bus.read::<u16>(BIOS_IF);
+        if discard && !self.intr_waiting {
+            bus.write::<u16>(BIOS_IF, flags & !mask);
+        } else if flags & mask != 0 {
+            bus.write::<u16>(BIOS_IF, flags & !mask);
+            self.intr_waiting = false;
+            return self.return_from_swi(bus, false);
+        }
+        self.intr_waiting = true;
+        bus.write::<u8>(0x0400_0301, 0x00);
+        self.return_from_swi(bus, true);
+    }
+
+    fn div(&mut self, num: i32, denom: i32) {
+        let (quot, rem) = if denom == 0 {
+            warn!("Division of {num} by zero");
+            (if num < 0 { -1 } else { 1 }, num)
+        } else {
+            (num.wrapping_div(denom), num.wrapping_rem(denom))
+        };
+        self.regs.set_reg_i(0, quot as u32);
+        self.regs.set_reg_i(1, rem as u32);
+        self.regs.set_reg_i(3, quot.unsigned_abs());
+    }
+
+    fn cpu_set(bus: &mut Sysbus, src: u32, dest: u32, control: u32) {
+        // Copying from the BIOS is refused
+        if src & 0x0E00_0000 == 0 {
+            return;
+        }
+        let count = control & 0x1F_FFFF;
+        let fill = control & 1 << 24 != 0;
+        if control & 1 << 26 != 0 {
+            let (src, dest) = (src & !0x3, dest & !0x3);
+            for i in 0..count {
+                let value = bus.read::<u32>(if fill { src } else { src + i * 4 });
+                bus.write::<u32>(dest + i * 4, value);
+            }
+        } else {
+            let (src, dest) = (src & !0x1, dest & !0x1);
+            for i in 0..count {
+                let value = bus.read::<u16>(if fill { src } else { src + i * 2 });
+                bus.write::<u16>(dest + i * 2, value);
+            }
+        }
+    }
+
+    fn cpu_fast_set(bus: &mut Sysbus, src: u32, dest: u32, control: u32) {
+        if src & 0x0E00_0000 == 0 {
+            return;
+        }
+        // Always copies blocks of 8 words
+        let count = ((control & 0x1F_FFFF) + 7) & !0x7;
+        let fill = control & 1 << 24 != 0;
+        let (src, dest) = (src & !0x3, dest & !0x3);
+        for i in 0..count {
+            let value = bus.read::<u32>(if fill { src } else { src + i * 4 });
+            bus.write::<u32>(dest + i * 4, value);
+        }
+    }
+
+    fn bg_affine_set(bus: &mut Sysbus, src: u32, dest: u32, count: u32) {
+        for i in 0..count {
+            let src = src + i * 20;
+            let dest = dest + i * 16;
+            let origin_x = bus.read::<u32>(src) as i32;
+            let origin_y = bus.read::<u32>(src + 4) as i32;
+            let center_x = bus.read::<u16>(src + 8) as i16 as i32;
+            let center_y = bus.read::<u16>(src + 10) as i16 as i32;
+            let scale_x = bus.read::<u16>(src + 12) as i16 as i32;
+            let scale_y = bus.read::<u16>(src + 14) as i16 as i32;
+            let angle = (bus.read::<u16>(src + 16) >> 8) as u8;
+
+            let [pa, pb, pc, pd] = affine_params(scale_x, scale_y, angle);
+            let start_x = origin_x - (pa * center_x + pb * center_y);
+            let start_y = origin_y - (pc * center_x + pd * center_y);
+            for (j, param) in [pa, pb, pc, pd].into_iter().enumerate() {
+                bus.write::<u16>(dest + j as u32 * 2, param as u16);
+            }
+            bus.write::<u32>(dest + 8, start_x as u32);
+            bus.write::<u32>(dest + 12, start_y as u32);
+        }
+    }
+
+    fn obj_affine_set(bus: &mut Sysbus, src: u32, dest: u32, count: u32, stride: u32) {
+        for i in 0..count {
+            let src = src + i * 8;
+            let scale_x = bus.read::<u16>(src) as i16 as i32;
+            let scale_y = bus.read::<u16>(src + 2) as i16 as i32;
+            let angle = (bus.read::<u16>(src + 4) >> 8) as u8;
+
+            let params = affine_params(scale_x, scale_y, angle);
+            for (j, param) in params.into_iter().enumerate() {
+                bus.write::<u16>(dest + (i * 4 + j as u32) * stride, param as u16);
+            }
+        }
+    }
+
+    fn bit_unpack(bus: &mut Sysbus, mut src: u32, mut dest: u32, info: u32) {
+        let len = bus.read::<u16>(info) as u32;
+        let src_width = bus.read::<u8>(info + 2) as u32;
+        let dest_width = bus.read::<u8>(info + 3) as u32;
+        let offset = bus.read::<u32>(info + 4);
+        let offset_zero = offset & 1 << 31 != 0;
+        let offset = offset & !(1 << 31);
+        if !matches!(src_width, 1 | 2 | 4 | 8) || !matches!(dest_width, 1 | 2 | 4 | 8 | 16 | 32) {
+            warn!("Invalid BitUnPack widths {src_width} -> {dest_width}");
+            return;
+        }
+
+        let mut word = 0u64;
+        let mut bits = 0;
+        for _ in 0..len {
+            let byte = bus.read::<u8>(src) as u32;
+            src += 1;
+            for shift in (0..8).step_by(src_width as usize) {
+                let mut value = byte >> shift & ((1 << src_width) - 1);
+                if value != 0 || offset_zero {
+                    value = value.wrapping_add(offset);
+                }
+                word |= (value as u64 & ((1 << dest_width) - 1)) << bits;
+                bits += dest_width;
+                if bits == 32 {
+                    bus.write::<u32>(dest, word as u32);
+                    dest += 4;
+                    word = 0;
+                    bits = 0;
+                }
+            }
+        }
+    }
+
+    fn huffman_decompress(bus: &mut Sysbus, src: u32, mut dest: u32) {
+        let header = bus.read::<u32>(src);
+        let width = header & 0xF;
+        let size = header >> 8;
+        if !matches!(width, 1 | 2 | 4 | 8) {
+            warn!("Invalid Huffman data width {width}");
+            return;
+        }
+
+        let root = src + 5;
+        let mut stream = src + 4 + (bus.read::<u8>(src + 4) as u32 + 1) * 2;
+        let mut node_addr = root;
+        let mut node = bus.read::<u8>(root) as u32;
+        let mut word = 0;
+        let mut bits = 0;
+        let mut written = 0;
+        while written < size {
+            let data = bus.read::<u32>(stream);
+            stream += 4;
+            for bit in (0..32).rev() {
+                let right = data >> bit & 1;
+                let child = (node_addr & !1) + (node & 0x3F) * 2 + 2 + right;
+                if node & (0x80 >> right) != 0 {
+                    word |= (bus.read::<u8>(child) as u32 & ((1 << width) - 1)) << bits;
+                    bits += width;
+                    node_addr = root;
+                    node = bus.read::<u8>(root) as u32;
+                } else {
+                    node_addr = child;
+                    node = bus.read::<u8>(child) as u32;
+                }
+                if bits == 32 {
+                    bus.write::<u32>(dest, word);
+                    dest += 4;
+                    written += 4;
+                    word = 0;
+                    bits = 0;
+                    if written >= size {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // The WRAM variants of the decompression functions write bytes
+    fn write_bytes(bus: &mut Sysbus, dest: u32, data: &[u8]) {
+        for (i, &byte) in data.iter().enumerate() {
+            bus.write::<u8>(dest + i as u32, byte);
+        }
+    }
+
+    // VRAM can't be written a byte at a time
+    fn write_halfwords(bus: &mut Sysbus, dest: u32, data: &[u8]) {
+        for (i, pair) in data.chunks(2).enumerate() {
+            let value = pair[0] as u16 | (*pair.get(1).unwrap_or(&0) as u16) << 8;
+            bus.write::<u16>(dest + i as u32 * 2, value);
+        }
+    }
+}
+
+fn lz77_decompress(bus: &Sysbus, mut src: u32) -> Vec<u8> {
+    let size = (bus.read::<u32>(src) >> 8) as usize;
+    src += 4;
+    let mut data = Vec::with_capacity(size);
+    let mut next_byte = || {
+        let byte = bus.read::<u8>(src);
+        src += 1;
+        byte
+    };
+
+    while data.len() < size {
+        let flags = next_byte();
+        for block in 0..8 {
+            if data.len() >= size {
+                break;
+            }
+            if flags & 0x80 >> block == 0 {
+                data.push(next_byte());
+                continue;
+            }
+            let (hi, lo) = (next_byte() as usize, next_byte() as usize);
+            let len = (hi >> 4) + 3;
+            let disp = ((hi & 0xF) << 8 | lo) + 1;
+            if disp > data.len() {
+                warn!("Invalid LZ77 displacement {disp}");
+                return data;
+            }
+            for _ in 0..len.min(size - data.len()) {
+                data.push(data[data.len() - disp]);
+            }
+        }
+    }
+    data
+}
+
+fn rl_decompress(bus: &Sysbus, mut src: u32) -> Vec<u8> {
+    let size = (bus.read::<u32>(src) >> 8) as usize;
+    src += 4;
+    let mut data = Vec::with_capacity(size);
+    let mut next_byte = || {
+        let byte = bus.read::<u8>(src);
+        src += 1;
+        byte
+    };
+
+    while data.len() < size {
+        let flag = next_byte() as usize;
+        if flag & 0x80 != 0 {
+            let len = (flag & 0x7F) + 3;
+            let byte = next_byte();
+            data.resize(data.len() + len.min(size - data.len()), byte);
+        } else {
+            for _ in 0..((flag & 0x7F) + 1).min(size - data.len()) {
+                data.push(next_byte());
+            }
+        }
+    }
+    data
+}
+
+// Every unit after the first one is stored as the difference to the previous one
+fn diff_unfilter(bus: &Sysbus, src: u32) -> Vec<u8> {
+    let header = bus.read::<u32>(src);
+    let size = (header >> 8) as usize;
+    let mut data = Vec::with_capacity(size);
+    if header & 0xF == 2 {
+        let mut value = 0u16;
+        for i in (0..size as u32).step_by(2) {
+            value = value.wrapping_add(bus.read::<u16>(src + 4 + i));
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.truncate(size);
+    } else {
+        let mut value = 0u8;
+        for i in 0..size as u32 {
+            value = value.wrapping_add(bus.read::<u8>(src + 4 + i));
+            data.push(value);
+        }
+    }
+    data
+}
+
+// The BIOS keeps a table of 256 sines as 1.14 fixed point, rounded towards zero
+fn sin(angle: u8) -> i32 {
+    ((angle as f64 * TAU / 256.0).sin() * 16384.0) as i32
+}
+
+// Rotation and scaling matrix for 8.8 fixed point scales
+fn affine_params(scale_x: i32, scale_y: i32, angle: u8) -> [i32; 4] {
+    let sin = sin(angle);
+    let cos = self::sin(angle.wrapping_add(64));
+    [
+        (scale_x * cos) >> 14,
+        -((scale_x * sin) >> 14),
+        (scale_y * sin) >> 14,
+        (scale_y * cos) >> 14,
+    ]
+}
+
+// Polynomial approximation of the BIOS for a 1.14 tangent, returns the angle along with
+// the values it leaves in r1 and r3
+fn arctan(tan: i32) -> (i32, i32, i32) {
+    let a = -(tan.wrapping_mul(tan) >> 14);
+    let b = [0x390, 0x91C, 0xFB6, 0x16AA, 0x2081, 0x3651, 0xA2F9]
+        .into_iter()
+        .fold(0xA9, |b: i32, c| (b.wrapping_mul(a) >> 14) + c);
+    (tan.wrapping_mul(b) >> 16, a, b)
+}
+
+// Angle of the vector (x, y) with 0x10000 being a full turn
+fn arctan2(x: i32, y: i32) -> u16 {
+    let atan = |num: i32, denom: i32| arctan((num << 14).wrapping_div(denom)).0;
+    let angle = match (x, y) {
+        (x, 0) if x >= 0 => 0,
+        (_, 0) => 0x8000,
+        (0, y) if y >= 0 => 0x4000,
+        (0, _) => 0xC000,
+        (x, y) if y >= 0 && x >= 0 && x >= y => atan(y, x),
+        (x, y) if y >= 0 && x < 0 && -x >= y => atan(y, x) + 0x8000,
+        (x, y) if y >= 0 => 0x4000 - atan(x, y),
+        (x, y) if x <= 0 && -x > -y => atan(y, x) + 0x8000,
+        (x, y) if x > 0 && x >= -y => atan(y, x) + 0x10000,
+        (x, y) => 0xC000 - atan(x, y),
+    };
+    angle as u16
+}
@@ -1,10 +1,19 @@
 #[allow(clippy::module_inception)]
 mod arm;
+pub mod disasm;
+pub(crate) mod hle;
 pub mod registers;
 mod thumb;
+pub mod trace;
 
-use self::registers::{Mode, Reg, Registers};
-use crate::io::{memory::MemoryValue, Cycle, MemoryAccess, Sysbus};
+use self::{
+    registers::{Mode, Reg, Registers},
+    trace::Tracer,
+};
+use crate::{
+    io::{memory::MemoryValue, Cycle, MemoryAccess, Sysbus},
+    state::impl_save_state,
+};
 use num::cast;
 use std::mem::size_of;
 
@@ -17,11 +26,20 @@ pub struct Arm7tdmi {
     pipeline: [u32; 2],
     next_access: MemoryAccess,
     internal: bool,
-
-    #[cfg(feature = "decode")]
-    decode_log: std::fs::File,
+    // Set while an HLE IntrWait is halted waiting for its interrupt
+    intr_waiting: bool,
+    // Debugging, not part of save states
+    tracer: Option<Box<Tracer>>,
 }
 
+impl_save_state!(Arm7tdmi {
+    regs,
+    pipeline,
+    next_access,
+    internal,
+    intr_waiting
+});
+
 impl Arm7tdmi {
     pub fn new(skip_bios: bool, bus: &mut Sysbus) -> Self {
         let mut arm = Self {
@@ -29,9 +47,8 @@ impl Arm7tdmi {
             pipeline: [0; 2],
             next_access: MemoryAccess::N,
             internal: false,
-
-            #[cfg(feature = "decode")]
-            decode_log: std::fs::File::create("decode.log").unwrap(),
+            intr_waiting: false,
+            tracer: None,
         };
 
         if skip_bios {
@@ -47,6 +64,7 @@ impl Arm7tdmi {
         self.pipeline = [0; 2];
         self.next_access = MemoryAccess::N;
         self.internal = false;
+        self.intr_waiting = false;
         if skip_bios {
             self.regs.skip_bios();
         }
@@ -67,17 +85,18 @@ impl Arm7tdmi {
     {
         bus.setup_openbus(self.regs.pc, self.regs.get_t(), &self.pipeline);
         let val = bus.read(addr);
-        bus.inc_clock(
-            self.next_access,
-            addr,
-            match size_of::<T>() {
-                1 => 0,
-                2 => 1,
-                4 => 2,
-                _ => unreachable!(),
-            },
-        );
-        self.next_access = access;
+        self.clock_access::<T>(bus, access, addr);
+        val
+    }
+
+    /// Like `read`, for instruction fetches which aren't caught by watchpoints
+    pub fn fetch<T>(&mut self, bus: &mut Sysbus, access: MemoryAccess, addr: u32) -> T
+    where
+        T: MemoryValue,
+    {
+        bus.setup_openbus(self.regs.pc, self.regs.get_t(), &self.pipeline);
+        let val = bus.fetch(addr);
+        self.clock_access::<T>(bus, access, addr);
         val
     }
 
@@ -86,6 +105,14 @@ impl Arm7tdmi {
         T: MemoryValue,
     {
         bus.setup_openbus(self.regs.pc, self.regs.get_t(), &self.pipeline);
+        self.clock_access::<T>(bus, access, addr);
+        bus.write(addr, value);
+    }
+
+    fn clock_access<T>(&mut self, bus: &mut Sysbus, access: MemoryAccess, addr: u32)
+    where
+        T: MemoryValue,
+    {
         bus.inc_clock(
             self.next_access,
             addr,
@@ -97,14 +124,13 @@ impl Arm7tdmi {
             },
         );
         self.next_access = access;
-        bus.write(addr, value);
     }
 
     pub fn instruction_prefetch<T>(&mut self, bus: &mut Sysbus, access: MemoryAccess)
     where
         T: MemoryValue,
     {
-        self.pipeline[1] = cast::<T, u32>(self.read(bus, access, self.regs.pc)).unwrap();
+        self.pipeline[1] = cast::<T, u32>(self.fetch(bus, access, self.regs.pc)).unwrap();
         self.internal = false;
     }
 
@@ -120,10 +146,10 @@ impl Arm7tdmi {
         }
         self.regs.change_mode(Mode::Irq);
         let lr = if self.regs.get_t() {
-            self.read::<u16>(bus, MemoryAccess::N, self.regs.pc);
+            self.fetch::<u16>(bus, MemoryAccess::N, self.regs.pc);
             self.regs.pc.wrapping_sub(2).wrapping_add(4)
         } else {
-            self.read::<u32>(bus, MemoryAccess::N, self.regs.pc);
+            self.fetch::<u32>(bus, MemoryAccess::N, self.regs.pc);
             self.regs.pc.wrapping_sub(4).wrapping_add(4)
         };
         self.regs.set_reg(Reg::R14, lr);
@@ -132,6 +158,56 @@ impl Arm7tdmi {
         self.regs.pc = 0x18;
         self.fill_arm_instr_buffer(bus);
     }
+
+    /// Enters the undefined instruction vector from the instruction that is executing.
+    /// The GBA has no coprocessors, so their instructions end up here too.
+    fn undefined_exception(&mut self, bus: &mut Sysbus, instr: u32) {
+        // PC is already one instruction ahead, the exception returns after the instruction
+        let lr = self.next_pc();
+        let addr = lr.wrapping_sub(if self.regs.get_t() { 2 } else { 4 });
+        warn!("Undefined instruction 0x{instr:08X} at 0x{addr:08X}");
+
+        if self.regs.get_t() {
+            self.instruction_prefetch::<u16>(bus, MemoryAccess::N);
+        } else {
+            self.instruction_prefetch::<u32>(bus, MemoryAccess::N);
+        }
+        self.internal(bus);
+        self.regs.change_mode(Mode::Undefined);
+        self.regs.set_reg(Reg::R14, lr);
+        self.regs.set_t(false);
+        self.regs.set_i(true);
+        self.regs.pc = 0x4;
+        self.fill_arm_instr_buffer(bus);
+    }
+
+    /// Address of the instruction that is executed next
+    pub fn next_pc(&self) -> u32 {
+        self.regs
+            .pc
+            .wrapping_sub(if self.regs.get_t() { 2 } else { 4 })
+    }
+
+    /// Continues execution at `addr` in the current state. The pipeline is refilled with
+    /// debug reads, so this takes no time and also picks up code patched by a debugger.
+    pub fn set_next_pc(&mut self, bus: &Sysbus, addr: u32) {
+        let thumb = self.regs.get_t();
+        let (size, addr) = if thumb {
+            (2, addr & !0x1)
+        } else {
+            (4, addr & !0x3)
+        };
+        let fetch = |addr: u32| {
+            if thumb {
+                bus.debug_read16(addr) as u32
+            } else {
+                bus.debug_read32(addr)
+            }
+        };
+        self.pipeline = [fetch(addr), fetch(addr.wrapping_add(size))];
+        self.regs.pc = addr.wrapping_add(size);
+        self.next_access = MemoryAccess::N;
+    }
 }
 
 impl Arm7tdmi {
@@ -1,5 +1,6 @@
 use std::fmt;
 
+use crate::state::impl_save_state;
 use fluorite_common::{bitfield, traits::UnsafeFrom};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -37,6 +38,18 @@ pub struct Registers {
     spsr: [StatusRegister; 5],
 }
 
+impl_save_state!(Registers {
+    usr,
+    fiq,
+    svc,
+    abt,
+    irq,
+    und,
+    pc,
+    cpsr,
+    spsr
+});
+
 impl Registers {
     pub fn new() -> Self {
         let mut ret = Self {
@@ -47,7 +60,8 @@ impl Registers {
             irq: [0; 2],
             und: [0; 2],
             pc: 0,
-            cpsr: StatusRegister::reset(),
+            // The CPU comes out of reset in ARM state and supervisor mode with interrupts off
+            cpsr: StatusRegister(0xC0 | Mode::Supervisor as u32),
             spsr: [StatusRegister::reset(); 5],
         };
 
@@ -64,7 +78,15 @@ impl Registers {
     }
 
     pub fn get_reg(&self, reg: Reg) -> u32 {
-        let mode = self.cpsr.mode();
+        self.get_banked(self.cpsr.mode(), reg)
+    }
+
+    pub fn set_reg(&mut self, reg: Reg, value: u32) {
+        self.set_banked(self.cpsr.mode(), reg, value)
+    }
+
+    /// Reads `reg` as seen from `mode`, which doesn't have to be the current mode
+    pub fn get_banked(&self, mode: Mode, reg: Reg) -> u32 {
         use Reg::*;
         match reg {
             R0 | R1 | R2 | R3 | R4 | R5 | R6 | R7 => self.usr[reg as usize],
@@ -93,8 +115,7 @@ impl Registers {
         }
     }
 
-    pub fn set_reg(&mut self, reg: Reg, value: u32) {
-        let mode = self.cpsr.mode();
+    pub fn set_banked(&mut self, mode: Mode, reg: Reg, value: u32) {
         use Reg::*;
         match reg {
             R0 | R1 | R2 | R3 | R4 | R5 | R6 | R7 => self.usr[reg as usize] = value,
@@ -222,7 +243,7 @@ impl Registers {
 }
 
 #[repr(u8)]
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Mode {
     User = 0b10000,
     Fiq = 0b10001,
@@ -233,6 +254,15 @@ pub enum Mode {
     Undefined = 0b11011,
 }
 
+impl Mode {
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        use Mode::*;
+        [User, Fiq, Irq, Supervisor, Abort, System, Undefined]
+            .into_iter()
+            .find(|&mode| mode as u32 == bits & 0x1F)
+    }
+}
+
 impl UnsafeFrom<u8> for Mode {
     #[inline]
     unsafe fn from(raw: u8) -> Self {
@@ -262,6 +292,8 @@ bitfield! {
     }
 }
 
+impl_save_state!(StatusRegister { 0 });
+
 impl StatusRegister {
     pub const fn reset() -> Self {
         Self(Mode::System as u32)
@@ -10,53 +10,17 @@ include!(concat!(env!("OUT_DIR"), "/thumb_lut.rs"));
 impl Arm7tdmi {
     pub(super) fn fill_thumb_instr_buffer(&mut self, bus: &mut Sysbus) {
         self.regs.pc &= !0x1;
-        self.pipeline[0] = self.read::<u16>(bus, MemoryAccess::S, self.regs.pc & !0x1) as u32;
+        self.pipeline[0] = self.fetch::<u16>(bus, MemoryAccess::S, self.regs.pc & !0x1) as u32;
         self.regs.pc = self.regs.pc.wrapping_add(2);
 
-        self.pipeline[1] = self.read::<u16>(bus, MemoryAccess::S, self.regs.pc & !0x1) as u32;
-    }
-
-    #[inline]
-    #[cfg(feature = "decode")]
-    pub fn decode_thumb_instr(addr: u32, instr: u16) -> String {
-        use yaxpeax_arch::{Arch, Decoder, U8Reader};
-        use yaxpeax_arm::armv7::ARMv7;
-
-        let data = [(instr & 0xFF) as u8, (instr >> 8 & 0xFF) as u8];
-        let mut reader = U8Reader::new(&data);
-        let decoder = <ARMv7 as Arch>::Decoder::default_thumb();
-        match decoder.decode(&mut reader) {
-            Ok(i) => format!("{:08X?} [  {:04X?}  ] -> {i}", addr, instr,),
-            Err(e) => format!("{e:?}"),
-        }
+        self.pipeline[1] = self.fetch::<u16>(bus, MemoryAccess::S, self.regs.pc & !0x1) as u32;
     }
 
     pub(super) fn emulate_thumb_instr(&mut self, bus: &mut Sysbus) {
         let instr = self.pipeline[0] as u16;
 
-        #[cfg(feature = "decode")]
-        {
-            use std::io::Write;
-
-            let reg = &self.regs;
-
-            writeln!(
-                self.decode_log,
-                "{:<50}  ({:08X} {:08X} {:08X} {:08X} {:08X}) {}",
-                Self::decode_thumb_instr(self.regs.pc.wrapping_sub(2), instr),
-                reg.get_reg_i(0),
-                reg.get_reg_i(1),
-                reg.get_reg_i(2),
-                reg.get_reg_i(3),
-                reg.get_reg_i(11),
-                format!(
-                    "N: {} Z: {} C: {} V {}",
-                    reg.get_n() as u8,
-                    reg.get_z() as u8,
-                    reg.get_c() as u8,
-                    reg.get_v() as u8
-                )
-            );
+        if self.tracer.is_some() {
+            self.trace(instr as u32);
         }
 
         self.pipeline[0] = self.pipeline[1];
@@ -578,6 +542,9 @@ impl Arm7tdmi {
     fn thumb_software_interrupt(&mut self, io: &mut Sysbus, instr: u16) {
         assert_eq!(instr >> 8 & 0xFF, 0b11011111);
         self.instruction_prefetch::<u16>(io, MemoryAccess::N);
+        if io.is_hle_bios() {
+            return self.hle_software_interrupt(io, (instr & 0xFF) as u8);
+        }
         self.regs.change_mode(Mode::Supervisor);
         self.regs.set_reg(Reg::R14, self.regs.pc.wrapping_sub(2));
         self.regs.set_t(false);
@@ -627,7 +594,7 @@ impl Arm7tdmi {
         }
     }
 
-    fn undefined_instr_thumb(&mut self, _: &mut Sysbus, _: u16) {
-        panic!("Undefined Thumb Instruction!")
+    fn undefined_instr_thumb(&mut self, bus: &mut Sysbus, instr: u16) {
+        self.undefined_exception(bus, instr as u32);
     }
 }
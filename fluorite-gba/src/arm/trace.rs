@@ -0,0 +1,156 @@
+//! Logging of every executed instruction, for diffing against other emulators
+
+use super::{
+    disasm::{disasm_arm, disasm_thumb},
+    registers::{Mode, Reg},
+    Arm7tdmi,
+};
+use std::{collections::VecDeque, fmt, ops::Range};
+
+pub type TraceSink = Box<dyn FnMut(&TraceEntry) + Send>;
+
+/// The state of the CPU right before an instruction is executed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub addr: u32,
+    /// Thumb opcodes are followed by the next halfword in the upper half, like in a
+    /// word read from `addr`
+    pub opcode: u32,
+    pub thumb: bool,
+    /// The registers of the current mode. PC reads ahead like it does for the instruction.
+    pub regs: [u32; 16],
+    pub cpsr: u32,
+}
+
+impl TraceEntry {
+    pub fn disasm(&self) -> String {
+        if self.thumb {
+            disasm_thumb(self.addr, self.opcode)
+        } else {
+            disasm_arm(self.addr, self.opcode)
+        }
+    }
+}
+
+/// A line like `<r0> .. <r15> cpsr: <cpsr> | <opcode>: <disassembly>` in hex, with Thumb
+/// opcodes padded to the width of ARM ones so the columns of a log line up for diffing
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for reg in self.regs {
+            write!(f, "{reg:08X} ")?;
+        }
+        write!(f, "cpsr: {:08X} | ", self.cpsr)?;
+        if self.thumb {
+            write!(f, "    {:04X}", self.opcode & 0xFFFF)?;
+        } else {
+            write!(f, "{:08X}", self.opcode)?;
+        }
+        write!(f, ": {}", self.disasm())
+    }
+}
+
+/// Which instructions are traced, everything by default
+#[derive(Clone, Debug, Default)]
+pub struct TraceFilter {
+    pub range: Option<Range<u32>>,
+    pub mode: Option<Mode>,
+    pub thumb: Option<bool>,
+}
+
+impl TraceFilter {
+    fn matches(&self, cpu: &Arm7tdmi, addr: u32) -> bool {
+        self.range
+            .as_ref()
+            .map_or(true, |range| range.contains(&addr))
+            && self.mode.map_or(true, |mode| mode == cpu.regs.get_mode())
+            && self.thumb.map_or(true, |thumb| thumb == cpu.regs.get_t())
+    }
+}
+
+enum TraceOutput {
+    Sink(TraceSink),
+    // The most recent entries, for a look at what led up to a crash or breakpoint
+    Buffer(VecDeque<TraceEntry>, usize),
+}
+
+pub(crate) struct Tracer {
+    filter: TraceFilter,
+    output: TraceOutput,
+}
+
+impl Tracer {
+    pub fn with_sink(filter: TraceFilter, sink: TraceSink) -> Self {
+        Self {
+            filter,
+            output: TraceOutput::Sink(sink),
+        }
+    }
+
+    pub fn with_buffer(filter: TraceFilter, capacity: usize) -> Self {
+        Self {
+            filter,
+            output: TraceOutput::Buffer(VecDeque::with_capacity(capacity), capacity),
+        }
+    }
+
+    /// The buffered entries from oldest to newest, nothing when tracing to a sink
+    pub fn entries(&self) -> Vec<TraceEntry> {
+        match &self.output {
+            TraceOutput::Sink(_) => Vec::new(),
+            TraceOutput::Buffer(entries, _) => entries.iter().copied().collect(),
+        }
+    }
+
+    fn log(&mut self, entry: TraceEntry) {
+        match &mut self.output {
+            TraceOutput::Sink(sink) => sink(&entry),
+            TraceOutput::Buffer(entries, capacity) => {
+                if entries.len() == *capacity {
+                    entries.pop_front();
+                }
+                if *capacity > 0 {
+                    entries.push_back(entry);
+                }
+            }
+        }
+    }
+}
+
+impl Arm7tdmi {
+    pub(crate) fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer.map(Box::new)
+    }
+
+    pub(crate) fn tracer(&self) -> Option<&Tracer> {
+        self.tracer.as_deref()
+    }
+
+    /// Called with the instruction that is about to be executed
+    pub(super) fn trace(&mut self, opcode: u32) {
+        let thumb = self.regs.get_t();
+        let addr = self.next_pc();
+        let tracer = self.tracer.as_ref().unwrap();
+        if !tracer.filter.matches(self, addr) {
+            return;
+        }
+
+        let mut regs = [0; 16];
+        for (i, reg) in regs.iter_mut().enumerate() {
+            *reg = self.regs.get_reg_i(i as u32);
+        }
+        regs[15] = addr.wrapping_add(if thumb { 4 } else { 8 });
+        let opcode = if thumb {
+            opcode & 0xFFFF | self.pipeline[1] << 16
+        } else {
+            opcode
+        };
+        let entry = TraceEntry {
+            addr,
+            opcode,
+            thumb,
+            regs,
+            cpsr: self.regs.get_reg(Reg::Cpsr),
+        };
+        self.tracer.as_mut().unwrap().log(entry);
+    }
+}
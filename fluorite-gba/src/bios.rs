@@ -1,56 +1,108 @@
-use fluorite_common::num;
-use fluorite_common::num::NumCast;
-use fluorite_common::Ram;
-use std::io::Read;
-use std::mem::size_of;
-use std::{cell::Cell, fs::File, path::Path};
+use crate::{arm::hle::STUB_BIOS, io::memory::MemoryValue, state::impl_save_state};
+use num::FromPrimitive;
+use std::{cell::Cell, fmt, mem::size_of};
 
-const SIZE: usize = 16 * 1024;
+pub const SIZE: usize = 0x4000;
 
-const REPLACEMENT_BIOS: &[u8; SIZE] = include_bytes!("../../roms/gba_bios.bin");
+// Sum of all words, as returned by the GetBiosChecksum SWI. The second one is the GBA
+// BIOS of the Nintendo DS.
+const CHECKSUMS: [u32; 2] = [0xBAAE_187F, 0xBAAE_1880];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BiosError {
+    InvalidSize(usize),
+    ChecksumMismatch(u32),
+}
+
+impl fmt::Display for BiosError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BiosError::InvalidSize(size) => {
+                write!(f, "BIOS is {size} bytes instead of {SIZE}")
+            }
+            BiosError::ChecksumMismatch(sum) => write!(f, "unknown BIOS checksum {sum:08X}"),
+        }
+    }
+}
+
+impl std::error::Error for BiosError {}
 
 pub struct Bios {
-    data: Ram<SIZE>,
+    data: Box<[u8]>,
+    // Without a dump SWIs are emulated natively and only the stub in `arm::hle` is mapped
+    hle: bool,
+    // Last value fetched from the BIOS, read back once the CPU has left it
     latch: Cell<u32>,
 }
 
+impl_save_state!(Bios { latch });
+
 impl Bios {
+    /// The built-in replacement, used when no dump of the real BIOS is available
     pub fn new() -> Self {
         Self {
-            data: Ram::new(*REPLACEMENT_BIOS),
+            data: Box::new(STUB_BIOS),
+            hle: true,
             latch: Cell::new(0xE129F000),
         }
     }
 
-    pub fn load<P: AsRef<Path>>(&mut self, path: P) {
-        let mut file = File::open(path).unwrap();
-
-        let bytes_read = file
-            .read(self.data.data.as_mut())
-            .expect("Failed to read file");
+    /// Checks that `data` is a dump of the GBA BIOS
+    pub fn from_image(data: Vec<u8>) -> Result<Self, BiosError> {
+        if data.len() != SIZE {
+            return Err(BiosError::InvalidSize(data.len()));
+        }
+        let sum = data
+            .chunks(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .fold(0u32, |sum, word| sum.wrapping_add(word));
+        if !CHECKSUMS.contains(&sum) {
+            return Err(BiosError::ChecksumMismatch(sum));
+        }
 
-        assert!(bytes_read <= SIZE);
+        Ok(Self {
+            data: data.into_boxed_slice(),
+            hle: false,
+            latch: Cell::new(0xE129F000),
+        })
     }
 
-    pub fn read_byte(&self, pc: u32, addr: u32) -> u8 {
-        self.read(pc, addr)
+    pub fn reset(&mut self) {
+        self.latch.set(0xE129F000);
     }
 
-    pub fn read_half(&self, pc: u32, addr: u32) -> u16 {
-        self.read(pc, addr)
+    pub fn is_hle(&self) -> bool {
+        self.hle
     }
 
-    pub fn read_word(&self, pc: u32, addr: u32) -> u32 {
-        self.read(pc, addr)
-    }
+    /// The BIOS can only be read while executing it, otherwise the last fetched value is
+    /// returned
+    pub fn read<T>(&self, pc: u32, addr: u32) -> T
+    where
+        T: MemoryValue,
+    {
+        if pc < SIZE as u32 {
+            let addr = (addr & !0x3) as usize;
+            self.latch.set(u32::from_le_bytes(
+                self.data[addr..addr + 4].try_into().unwrap(),
+            ));
+        }
 
-    fn read<T: NumCast>(&self, pc: u32, addr: u32) -> T {
-        let addr = addr & !(size_of::<T>() as u32 - 1);
+        let mask = match size_of::<T>() {
+            1 => 0xFF,
+            2 => 0xFFFF,
+            4 => 0xFFFF_FFFF,
+            _ => unreachable!(),
+        };
+        FromPrimitive::from_u32((self.latch.get() >> ((addr & 3) * 8)) & mask).unwrap()
+    }
 
-        if pc < 0x4000 {
-            self.latch.set(self.data.read_fast(addr & !0x3))
-        }
+    /// Reads the BIOS regardless of the PC, leaving the latch alone
+    pub fn peek(&self, addr: u32) -> u8 {
+        self.data[addr as usize % SIZE]
+    }
 
-        num::cast(self.latch.get() >> (8 * (addr & 0x3))).unwrap()
+    pub fn set_latch(&mut self, value: u32) {
+        self.latch.set(value)
     }
 }
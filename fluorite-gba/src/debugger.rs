@@ -0,0 +1,291 @@
+//! Breakpoints, watchpoints and stepping for `Gba::run`. Nothing is checked while none
+//! of them are set, so a rom runs at full speed without a debugger.
+
+use crate::{
+    arm::{registers::Reg, Arm7tdmi},
+    io::Sysbus,
+};
+use std::cell::Cell;
+
+/// Why `Gba::run` returned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// All cycles ran
+    FrameEnd,
+    /// The next instruction is at the breakpoint at this address
+    Breakpoint(u32),
+    /// The last instruction or DMA transfer accessed a watched address
+    Watchpoint(WatchHit),
+    /// The step requested with `Gba::step` finished
+    Step,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Compares a register of the current mode with `value`. The comparison is unsigned and
+/// `R15` is the address of the next instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Condition {
+    pub reg: Reg,
+    pub cmp: Comparison,
+    pub value: u32,
+}
+
+impl Condition {
+    fn holds(&self, cpu: &Arm7tdmi) -> bool {
+        let reg = match self.reg {
+            Reg::R15 => cpu.next_pc(),
+            reg => cpu.regs.get_reg(reg),
+        };
+        match self.cmp {
+            Comparison::Eq => reg == self.value,
+            Comparison::Ne => reg != self.value,
+            Comparison::Lt => reg < self.value,
+            Comparison::Le => reg <= self.value,
+            Comparison::Gt => reg > self.value,
+            Comparison::Ge => reg >= self.value,
+        }
+    }
+}
+
+/// Stops before the instruction at `addr` is executed. Bit 0 of the address is ignored,
+/// so Thumb addresses from ELF symbols can be used as they are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Breakpoint {
+    pub addr: u32,
+    /// Only stops in Thumb state if true and in ARM state if false
+    pub thumb: Option<bool>,
+    pub condition: Option<Condition>,
+}
+
+impl Breakpoint {
+    pub fn new(addr: u32) -> Self {
+        Self {
+            addr,
+            thumb: None,
+            condition: None,
+        }
+    }
+
+    fn hit(&self, cpu: &Arm7tdmi) -> bool {
+        self.addr & !0x1 == cpu.next_pc()
+            && self.thumb.map_or(true, |thumb| thumb == cpu.regs.get_t())
+            && self
+                .condition
+                .map_or(true, |condition| condition.holds(cpu))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+/// Catches accesses to `len` bytes starting at `addr` by the CPU and DMA. Instruction
+/// fetches and the debug accessors of `Sysbus` aren't caught. Watching IO registers
+/// catches every access to them, not only ones that change their value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub addr: u32,
+    pub len: u32,
+    pub kind: WatchKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+    pub watchpoint: Watchpoint,
+    /// The accessed address, which may lie before the watched range for wide accesses
+    pub addr: u32,
+    /// `Read` or `Write`
+    pub kind: WatchKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// Executes one instruction
+    Into,
+    /// Like `Into`, but runs calls with BL or SWI until they return
+    Over,
+    /// Runs until the current function returns to the address in LR
+    Out,
+}
+
+#[derive(Clone, Copy)]
+enum StepState {
+    Into { executed: bool },
+    // Both the return address and the stack pointer have to be reached, so that
+    // recursive calls don't stop the step early
+    Until { addr: u32, sp: u32 },
+}
+
+pub(crate) struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    step: Option<StepState>,
+    // The instruction the last stop happened at, its breakpoints are skipped when resuming
+    resume_at: Option<u32>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            step: None,
+            resume_at: None,
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint)
+    }
+
+    /// Returns false if there was no such breakpoint
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        match self.breakpoints.iter().position(|&b| b == breakpoint) {
+            Some(i) => {
+                self.breakpoints.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear()
+    }
+
+    pub fn step(&mut self, step: Step, cpu: &Arm7tdmi, bus: &Sysbus) {
+        let pc = cpu.next_pc();
+        let sp = cpu.regs.get_reg(Reg::R13);
+        let call_len = if cpu.regs.get_t() {
+            match bus.debug_read16(pc) {
+                // The first half of BL, the second one follows
+                op if op & 0xF800 == 0xF000 => Some(4),
+                op if op & 0xF800 == 0xF800 || op & 0xFF00 == 0xDF00 => Some(2),
+                _ => None,
+            }
+        } else {
+            let op = bus.debug_read32(pc);
+            // BL and SWI, other than the unconditional space of later architectures
+            (op >> 28 != 0xF && matches!(op >> 24 & 0xF, 0xB | 0xF)).then_some(4)
+        };
+
+        self.step = Some(match (step, call_len) {
+            (Step::Over, Some(len)) => StepState::Until {
+                addr: pc.wrapping_add(len),
+                sp,
+            },
+            (Step::Out, _) => StepState::Until {
+                addr: cpu.regs.get_reg(Reg::R14) & !0x1,
+                sp,
+            },
+            _ => StepState::Into { executed: false },
+        });
+    }
+
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        !self.breakpoints.is_empty() || self.step.is_some()
+    }
+
+    /// Called before every instruction while the debugger or watchpoints are active
+    pub fn check(&mut self, cpu: &Arm7tdmi, bus: &Sysbus) -> Option<StopReason> {
+        let pc = cpu.next_pc();
+        let resuming = self.resume_at.take() == Some(pc);
+
+        let reason = if let Some(hit) = bus.watchpoints.take_hit() {
+            StopReason::Watchpoint(hit)
+        } else if self.step_done(cpu) {
+            self.step = None;
+            StopReason::Step
+        } else if let Some(breakpoint) = self.breakpoints.iter().find(|b| b.hit(cpu)) {
+            if resuming {
+                return None;
+            }
+            StopReason::Breakpoint(breakpoint.addr)
+        } else {
+            return None;
+        };
+        self.resume_at = Some(pc);
+        Some(reason)
+    }
+
+    fn step_done(&mut self, cpu: &Arm7tdmi) -> bool {
+        match &mut self.step {
+            Some(StepState::Into { executed }) => std::mem::replace(executed, true),
+            Some(StepState::Until { addr, sp }) => {
+                cpu.next_pc() == *addr && cpu.regs.get_reg(Reg::R13) >= *sp
+            }
+            None => false,
+        }
+    }
+}
+
+pub(crate) struct Watchpoints {
+    list: Vec<Watchpoint>,
+    // Reads only borrow the bus, the first hit is kept until the debugger takes it
+    hit: Cell<Option<WatchHit>>,
+}
+
+impl Watchpoints {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            hit: Cell::new(None),
+        }
+    }
+
+    pub fn add(&mut self, watchpoint: Watchpoint) {
+        self.list.push(watchpoint)
+    }
+
+    pub fn remove(&mut self, watchpoint: Watchpoint) -> bool {
+        match self.list.iter().position(|&w| w == watchpoint) {
+            Some(i) => {
+                self.list.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+        self.hit.set(None);
+    }
+
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        !self.list.is_empty()
+    }
+
+    #[cold]
+    pub fn check(&self, addr: u32, len: u32, kind: WatchKind) {
+        if self.hit.get().is_some() {
+            return;
+        }
+        let watchpoint = self.list.iter().copied().find(|w| {
+            (w.kind == kind || w.kind == WatchKind::Access)
+                && addr < w.addr.wrapping_add(w.len)
+                && w.addr < addr.wrapping_add(len)
+        });
+        self.hit.set(watchpoint.map(|watchpoint| WatchHit {
+            watchpoint,
+            addr,
+            kind,
+        }));
+    }
+
+    pub fn take_hit(&self) -> Option<WatchHit> {
+        self.hit.take()
+    }
+}
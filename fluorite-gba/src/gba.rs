@@ -1,11 +1,25 @@
-use crate::{arm::Arm7tdmi, io::Sysbus, AudioInterface};
+use crate::{
+    arm::{
+        trace::{TraceEntry, TraceFilter, Tracer},
+        Arm7tdmi,
+    },
+    bios::{Bios, BiosError},
+    debugger::{Breakpoint, Debugger, Step, StopReason, Watchpoint},
+    io::{keypad::KeyState, mgba_debug::DebugLevel, sio::LinkBackend, Sysbus},
+    state::{SaveState, StateError, StateReader, StateWriter},
+    AudioInterface,
+};
 use fluorite_common::{flume::Receiver, EasyCell};
-use std::path::Path;
+use std::{io, path::Path};
 
 pub struct Gba {
     pub cpu: Arm7tdmi,
     pub bus: Sysbus,
     pub next_frame_cycle: usize,
+    skip_bios: bool,
+    debugger: Debugger,
+    // Set when the debugger stopped `run` before all of its cycles ran
+    interrupted: bool,
 }
 
 pub type Pixels = Vec<u16>;
@@ -13,14 +27,24 @@ pub type Pixels = Vec<u16>;
 pub static AUDIO_DEVICE: EasyCell<&mut dyn AudioInterface> = EasyCell::new();
 
 impl Gba {
-    pub fn new(rx: Receiver<(u16, bool)>) -> Self {
-        let mut bus = Sysbus::new(rx);
+    /// `bios` is a dump of the real BIOS. Without one a built-in replacement is used, which
+    /// emulates the BIOS calls but can't show the boot logo.
+    pub fn new(bios: Option<Vec<u8>>) -> Result<Self, BiosError> {
+        let bios = match bios {
+            Some(data) => Bios::from_image(data)?,
+            None => Bios::new(),
+        };
+        let mut bus = Sysbus::new(bios);
+        bus.skip_bios();
 
-        Self {
+        Ok(Self {
             cpu: Arm7tdmi::new(true, &mut bus),
             bus,
             next_frame_cycle: 0,
-        }
+            skip_bios: true,
+            debugger: Debugger::new(),
+            interrupted: false,
+        })
     }
 
     pub fn load_audio(device: *mut dyn AudioInterface) {
@@ -29,18 +53,183 @@ impl Gba {
 
     pub fn reset(&mut self) {
         self.bus.reset();
-        self.cpu.reset(true, &mut self.bus);
+        let skip_bios = self.skip_bios || self.bus.is_hle_bios();
+        if skip_bios {
+            self.bus.skip_bios();
+        }
+        self.cpu.reset(skip_bios, &mut self.bus);
         self.next_frame_cycle = 0;
+        self.interrupted = false;
+    }
+
+    /// Whether `reset` starts the cartridge right away or powers on at the reset vector
+    /// and runs the boot logo first. Booting through the BIOS also runs its cartridge
+    /// header check, which locks up on a missing or invalid cartridge like the hardware.
+    /// The boot logo needs a real BIOS, it is always skipped otherwise.
+    pub fn set_skip_bios(&mut self, skip: bool) {
+        self.skip_bios = skip
     }
 
-    pub fn run(&mut self, cycles: usize) {
-        self.next_frame_cycle += cycles;
-        self.bus.poll_keypad_updates();
+    /// Runs for `cycles` cycles unless the debugger stops earlier. After a stop the next
+    /// call finishes the interrupted run instead of starting a new one.
+    pub fn run(&mut self, cycles: usize) -> StopReason {
+        if !self.interrupted {
+            self.next_frame_cycle += cycles;
+            self.bus.poll_keypad_updates();
+        }
+        self.interrupted = false;
         while self.bus.get_cycle() < self.next_frame_cycle {
+            if self.bus.is_halted() {
+                self.bus.idle(self.next_frame_cycle);
+                continue;
+            }
             self.bus.run_dma();
             self.cpu.handle_irq(&mut self.bus);
+            if self.debugger.is_active() || self.bus.watchpoints.is_active() {
+                if let Some(reason) = self.debugger.check(&self.cpu, &self.bus) {
+                    self.interrupted = true;
+                    return reason;
+                }
+            }
             self.cpu.emulate_instr(&mut self.bus);
         }
+        StopReason::FrameEnd
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.debugger.add_breakpoint(breakpoint)
+    }
+
+    /// Returns false if there was no such breakpoint
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        self.debugger.remove_breakpoint(breakpoint)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.debugger.clear_breakpoints()
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.bus.watchpoints.add(watchpoint)
+    }
+
+    /// Returns false if there was no such watchpoint
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        self.bus.watchpoints.remove(watchpoint)
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.bus.watchpoints.clear()
+    }
+
+    /// The next `run` returns `StopReason::Step` once the step is done. A halted CPU
+    /// waits for its interrupt first.
+    pub fn step(&mut self, step: Step) {
+        self.debugger.step(step, &self.cpu, &self.bus)
+    }
+
+    /// Calls `sink` before every instruction that passes `filter`
+    pub fn trace(&mut self, filter: TraceFilter, sink: impl FnMut(&TraceEntry) + Send + 'static) {
+        self.cpu
+            .set_tracer(Some(Tracer::with_sink(filter, Box::new(sink))))
+    }
+
+    /// Keeps the last `capacity` instructions that pass `filter` for `trace_buffer`
+    pub fn trace_to_buffer(&mut self, filter: TraceFilter, capacity: usize) {
+        self.cpu
+            .set_tracer(Some(Tracer::with_buffer(filter, capacity)))
+    }
+
+    /// The buffered instructions from oldest to newest
+    pub fn trace_buffer(&self) -> Vec<TraceEntry> {
+        self.cpu.tracer().map_or(Vec::new(), Tracer::entries)
+    }
+
+    pub fn stop_trace(&mut self) {
+        self.cpu.set_tracer(None)
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        self.cpu.save_state(&mut w);
+        self.bus.save_state(&mut w);
+        self.next_frame_cycle.save_state(&mut w);
+        w.finish(self.bus.gamepak.rom.hash, self.bus.is_hle_bios())
+    }
+
+    /// Restores a state created by `save_state`. A rejected state leaves the emulator
+    /// untouched.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let hle_bios = self.bus.is_hle_bios();
+        let r = StateReader::new(state, self.bus.gamepak.rom.hash, hle_bios)?;
+        // A malformed payload is only noticed halfway through loading it
+        let backup = self.save_state();
+        if let Err(err) = self.load_payload(r) {
+            let r = StateReader::new(&backup, self.bus.gamepak.rom.hash, hle_bios).unwrap();
+            self.load_payload(r).unwrap();
+            return Err(err);
+        }
+        self.interrupted = false;
+        self.bus.resume_link();
+        Ok(())
+    }
+
+    fn load_payload(&mut self, mut r: StateReader) -> Result<(), StateError> {
+        self.cpu.load_state(&mut r);
+        self.bus.load_state(&mut r);
+        self.next_frame_cycle.load_state(&mut r);
+        r.finish()
+    }
+
+    /// Plugs a link cable into the serial port. Two emulators in the same process are
+    /// connected by giving each one end of `Loopback::pair`.
+    pub fn set_link(&mut self, link: Box<dyn LinkBackend>) {
+        self.bus.set_link(link)
+    }
+
+    /// Key events are `(key, pressed)` pairs with a mask like `KeyState::A` as the key.
+    /// Events sent through `rx` are applied at the start of every frame.
+    pub fn set_key_receiver(&mut self, rx: Receiver<(u16, bool)>) {
+        self.bus.set_key_receiver(rx)
+    }
+
+    pub fn keys(&self) -> KeyState {
+        self.bus.keys()
+    }
+
+    /// Replaces the held keys. Unlike the key receiver this takes effect immediately, even
+    /// in the middle of a frame.
+    pub fn set_keys(&mut self, keys: KeyState) {
+        self.bus.set_keys(keys)
+    }
+
+    /// Presses the keys in the `KeyState` mask `keys`, leaving the others alone
+    pub fn press(&mut self, keys: u16) {
+        self.set_keys(KeyState(self.keys().0 | keys))
+    }
+
+    pub fn release(&mut self, keys: u16) {
+        self.set_keys(KeyState(self.keys().0 & !keys))
+    }
+
+    /// Receives the messages roms print through the debug port of mGBA. Without a sink
+    /// they are logged through the `log` crate.
+    pub fn set_debug_sink(&mut self, sink: impl FnMut(DebugLevel, &str) + Send + 'static) {
+        self.bus.set_debug_sink(Box::new(sink))
+    }
+
+    /// Turning persistence off keeps the emulator away from the save file next to the rom.
+    /// It has to be turned off before `load_rom` to also start with an empty backup.
+    pub fn set_persist(&mut self, persist: bool) {
+        self.bus.gamepak.set_persist(persist)
+    }
+
+    pub fn set_save_interval(&mut self, frames: usize) {
+        self.bus.gamepak.set_save_interval(frames)
+    }
+
+    pub fn flush_save(&mut self) -> io::Result<()> {
+        self.bus.gamepak.flush_save()
     }
 
     pub fn get_pixels(&self) -> &[u16] {
@@ -49,6 +238,16 @@ impl Gba {
 
     pub fn load_rom<P: AsRef<Path>>(&mut self, path: P) {
         // self.bus.rom = std::fs::read(path).unwrap().into_boxed_slice();
-        self.bus.gamepak.load(Some(path.as_ref()), None)
+        self.bus.gamepak.load(Some(path.as_ref()), None).unwrap()
+    }
+
+    /// Imports a save file into the backup of the loaded rom. The game only reads
+    /// its save on boot, so this is usually followed by a `reset`.
+    pub fn load_save<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.bus.gamepak.load(None, Some(path.as_ref()))
+    }
+
+    pub fn export_save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.bus.gamepak.export_save(path.as_ref())
     }
 }
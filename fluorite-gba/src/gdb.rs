@@ -0,0 +1,491 @@
+//! A GDB remote serial protocol server. Once a rom runs through `GdbStub::run`, debug it
+//! with `arm-none-eabi-gdb game.elf -ex "target remote localhost:<port>"`.
+
+use crate::{
+    arm::registers::{Mode, Reg},
+    debugger::{Breakpoint, Step, StopReason, WatchKind, Watchpoint},
+    gba::Gba,
+};
+use std::{
+    fmt::Write as _,
+    io::{self, ErrorKind, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    time::{Duration, Instant},
+};
+
+// How long `run` waits for commands while the rom is stopped
+const FRAME_TIME: Duration = Duration::from_micros(16_743);
+// Largest packet accepted from GDB, which also limits the size of memory reads
+const PACKET_SIZE: usize = 0x1000;
+
+// Registers after r0-r15 and the CPSR, in the order of `target_xml`
+const BANKED: [(&str, Mode, Reg); 27] = [
+    ("r8_usr", Mode::User, Reg::R8),
+    ("r9_usr", Mode::User, Reg::R9),
+    ("r10_usr", Mode::User, Reg::R10),
+    ("r11_usr", Mode::User, Reg::R11),
+    ("r12_usr", Mode::User, Reg::R12),
+    ("sp_usr", Mode::User, Reg::R13),
+    ("lr_usr", Mode::User, Reg::R14),
+    ("r8_fiq", Mode::Fiq, Reg::R8),
+    ("r9_fiq", Mode::Fiq, Reg::R9),
+    ("r10_fiq", Mode::Fiq, Reg::R10),
+    ("r11_fiq", Mode::Fiq, Reg::R11),
+    ("r12_fiq", Mode::Fiq, Reg::R12),
+    ("sp_fiq", Mode::Fiq, Reg::R13),
+    ("lr_fiq", Mode::Fiq, Reg::R14),
+    ("sp_irq", Mode::Irq, Reg::R13),
+    ("lr_irq", Mode::Irq, Reg::R14),
+    ("sp_svc", Mode::Supervisor, Reg::R13),
+    ("lr_svc", Mode::Supervisor, Reg::R14),
+    ("sp_abt", Mode::Abort, Reg::R13),
+    ("lr_abt", Mode::Abort, Reg::R14),
+    ("sp_und", Mode::Undefined, Reg::R13),
+    ("lr_und", Mode::Undefined, Reg::R14),
+    ("spsr_fiq", Mode::Fiq, Reg::Spsr),
+    ("spsr_irq", Mode::Irq, Reg::Spsr),
+    ("spsr_svc", Mode::Supervisor, Reg::Spsr),
+    ("spsr_abt", Mode::Abort, Reg::Spsr),
+    ("spsr_und", Mode::Undefined, Reg::Spsr),
+];
+const REGISTERS: usize = 17 + BANKED.len();
+
+pub struct GdbStub {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+    input: Vec<u8>,
+    stopped: bool,
+    stop_reply: String,
+}
+
+impl GdbStub {
+    /// Listens for a debugger on `port` of localhost. Port 0 picks a free one.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            stream: None,
+            input: Vec::new(),
+            stopped: false,
+            stop_reply: "S05".into(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Blocks until a debugger connects, to debug a rom from its first instruction
+    pub fn wait_for_debugger(&mut self) -> io::Result<()> {
+        self.listener.set_nonblocking(false)?;
+        let result = self.listener.accept();
+        self.listener.set_nonblocking(true)?;
+        self.attach(result?.0)
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Whether the debugger is holding the rom, `run` doesn't emulate anything then
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Runs a frame like `Gba::run` while serving the debugger, and returns whether the
+    /// frame ended. A debugger is attached here once it connects, which stops the rom.
+    /// While stopped this waits for commands for about a frame and returns false.
+    pub fn run(&mut self, gba: &mut Gba, cycles: usize) -> bool {
+        if self.stream.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = self.attach(stream) {
+                        warn!("Failed to attach GDB: {e}");
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+                Err(e) => warn!("Failed to accept GDB: {e}"),
+            }
+        }
+        if let Err(e) = self.serve(gba) {
+            if e.kind() == ErrorKind::UnexpectedEof {
+                info!("GDB detached");
+            } else {
+                warn!("Lost connection to GDB: {e}");
+            }
+            self.detach(gba);
+        }
+        if self.stopped {
+            return false;
+        }
+
+        let reason = gba.run(cycles);
+        if reason != StopReason::FrameEnd && self.stream.is_some() {
+            self.stop(reason);
+            let reply = std::mem::take(&mut self.stop_reply);
+            let result = self.send(&reply);
+            self.stop_reply = reply;
+            if let Err(e) = result {
+                warn!("Lost connection to GDB: {e}");
+                self.detach(gba);
+            }
+        }
+        reason == StopReason::FrameEnd
+    }
+
+    fn attach(&mut self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        info!("GDB attached from {}", stream.peer_addr()?);
+        self.stream = Some(stream);
+        self.input.clear();
+        self.stopped = true;
+        self.stop_reply = "S05".into();
+        Ok(())
+    }
+
+    fn detach(&mut self, gba: &mut Gba) {
+        self.stream = None;
+        self.stopped = false;
+        gba.clear_breakpoints();
+        gba.clear_watchpoints();
+    }
+
+    fn stop(&mut self, reason: StopReason) {
+        self.stopped = true;
+        self.stop_reply = match reason {
+            StopReason::Watchpoint(hit) => {
+                let kind = match hit.watchpoint.kind {
+                    WatchKind::Write => "watch",
+                    WatchKind::Read => "rwatch",
+                    WatchKind::Access => "awatch",
+                };
+                format!("T05{kind}:{:08x};", hit.addr)
+            }
+            _ => "S05".into(),
+        };
+    }
+
+    fn serve(&mut self, gba: &mut Gba) -> io::Result<()> {
+        if self.stream.is_none() {
+            return Ok(());
+        }
+        if !self.stopped {
+            self.receive(None)?;
+            return self.process(gba);
+        }
+
+        let deadline = Instant::now() + FRAME_TIME;
+        while self.stopped && self.stream.is_some() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            self.receive(Some(deadline - now))?;
+            self.process(gba)?;
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self, wait: Option<Duration>) -> io::Result<()> {
+        let stream = self.stream.as_mut().unwrap();
+        stream.set_nonblocking(wait.is_none())?;
+        stream.set_read_timeout(wait)?;
+
+        let mut buffer = [0; PACKET_SIZE];
+        match stream.read(&mut buffer) {
+            Ok(0) => Err(ErrorKind::UnexpectedEof.into()),
+            Ok(len) => {
+                self.input.extend_from_slice(&buffer[..len]);
+                Ok(())
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn process(&mut self, gba: &mut Gba) -> io::Result<()> {
+        while let Some(&first) = self.input.first() {
+            match first {
+                // Ctrl-C
+                0x03 => {
+                    self.input.remove(0);
+                    if !self.stopped {
+                        self.stopped = true;
+                        self.stop_reply = "S02".into();
+                        self.send("S02")?;
+                    }
+                }
+                b'$' => {
+                    let end = match self.input.iter().position(|&c| c == b'#') {
+                        Some(end) if end + 2 < self.input.len() => end,
+                        // Wait for the rest of the packet
+                        _ => return Ok(()),
+                    };
+                    let packet: Vec<u8> = self.input.drain(..end + 3).collect();
+                    let data = &packet[1..end];
+                    let checksum = std::str::from_utf8(&packet[end + 1..])
+                        .ok()
+                        .and_then(|sum| u8::from_str_radix(sum, 16).ok());
+                    if checksum != Some(Self::checksum(data)) {
+                        self.stream.as_mut().unwrap().write_all(b"-")?;
+                        continue;
+                    }
+                    self.stream.as_mut().unwrap().write_all(b"+")?;
+
+                    let data = String::from_utf8_lossy(data).into_owned();
+                    if let Some(reply) = self.handle(gba, &data) {
+                        self.send(&reply)?;
+                    }
+                    if matches!(data.as_bytes().first(), Some(b'D' | b'k')) {
+                        info!("GDB detached");
+                        self.detach(gba);
+                        return Ok(());
+                    }
+                }
+                // Acks, the connection is reliable so there is nothing to resend
+                _ => {
+                    self.input.remove(0);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${data}#{:02x}", Self::checksum(data.as_bytes()));
+        self.stream.as_mut().unwrap().write_all(packet.as_bytes())
+    }
+
+    fn checksum(data: &[u8]) -> u8 {
+        data.iter().fold(0, |sum, &c| sum.wrapping_add(c))
+    }
+
+    /// Returns the reply to `packet`, none while the rom is running again
+    fn handle(&mut self, gba: &mut Gba, packet: &str) -> Option<String> {
+        const ERROR: &str = "E01";
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "?" => self.stop_reply.clone(),
+            "g" => (0..REGISTERS)
+                .map(|n| hex_u32(read_register(gba, n).unwrap()))
+                .collect(),
+            "G" => {
+                let values: Option<Vec<_>> = (0..REGISTERS)
+                    .map(|n| args.get(n * 8..n * 8 + 8).and_then(parse_u32))
+                    .collect();
+                match values {
+                    Some(values)
+                        if values
+                            .iter()
+                            .enumerate()
+                            .all(|(n, &value)| write_register(gba, n, value)) =>
+                    {
+                        "OK".into()
+                    }
+                    _ => ERROR.into(),
+                }
+            }
+            "p" => usize::from_str_radix(args, 16)
+                .ok()
+                .and_then(|n| read_register(gba, n))
+                .map_or(ERROR.into(), hex_u32),
+            "P" => {
+                let written = args.split_once('=').and_then(|(n, value)| {
+                    let n = usize::from_str_radix(n, 16).ok()?;
+                    Some(write_register(gba, n, parse_u32(value)?))
+                });
+                (if written == Some(true) { "OK" } else { ERROR }).into()
+            }
+            "m" => match parse_range(args) {
+                Some((addr, len)) => (0..len.min(PACKET_SIZE as u32 / 2))
+                    .map(|i| format!("{:02x}", gba.bus.debug_read8(addr.wrapping_add(i))))
+                    .collect(),
+                None => ERROR.into(),
+            },
+            "M" => {
+                let write = args.split_once(':').and_then(|(range, data)| {
+                    let (addr, len) = parse_range(range)?;
+                    let bytes = parse_bytes(data)?;
+                    (bytes.len() == len as usize).then_some((addr, bytes))
+                });
+                match write {
+                    Some((addr, bytes)) => {
+                        for (i, byte) in bytes.into_iter().enumerate() {
+                            gba.bus.debug_write8(addr.wrapping_add(i as u32), byte);
+                        }
+                        // Refetch in case the next instructions were patched
+                        let pc = gba.cpu.next_pc();
+                        gba.cpu.set_next_pc(&gba.bus, pc);
+                        "OK".into()
+                    }
+                    None => ERROR.into(),
+                }
+            }
+            "c" | "s" => {
+                if let Ok(addr) = u32::from_str_radix(args, 16) {
+                    gba.cpu.set_next_pc(&gba.bus, addr);
+                }
+                if command == "s" {
+                    gba.step(Step::Into);
+                }
+                // The reply is sent once the rom stops again
+                self.stopped = false;
+                return None;
+            }
+            "Z" | "z" => match parse_breakpoint(args) {
+                Some((kind, addr, len)) => {
+                    let insert = command == "Z";
+                    let done = match kind {
+                        // The rom is never patched, so software and hardware
+                        // breakpoints are the same. GDB uses the latter for code in rom.
+                        0 | 1 if insert => {
+                            gba.add_breakpoint(Breakpoint::new(addr));
+                            true
+                        }
+                        0 | 1 => gba.remove_breakpoint(Breakpoint::new(addr)),
+                        2..=4 => {
+                            let watchpoint = Watchpoint {
+                                addr,
+                                len,
+                                kind: match kind {
+                                    2 => WatchKind::Write,
+                                    3 => WatchKind::Read,
+                                    _ => WatchKind::Access,
+                                },
+                            };
+                            if insert {
+                                gba.add_watchpoint(watchpoint);
+                                true
+                            } else {
+                                gba.remove_watchpoint(watchpoint)
+                            }
+                        }
+                        _ => return Some(String::new()),
+                    };
+                    (if done { "OK" } else { ERROR }).into()
+                }
+                None => ERROR.into(),
+            },
+            "D" => "OK".into(),
+            "k" => return None,
+            "H" => "OK".into(),
+            "q" if args.starts_with("Supported") => {
+                format!("PacketSize={PACKET_SIZE:x};qXfer:features:read+")
+            }
+            "q" if args == "Attached" => "1".into(),
+            "q" if args.starts_with("Xfer:features:read:target.xml:") => {
+                let xml = target_xml();
+                match parse_range(&args["Xfer:features:read:target.xml:".len()..]) {
+                    Some((offset, len)) => {
+                        let start = (offset as usize).min(xml.len());
+                        let end = (start + len as usize).min(xml.len());
+                        let more = if end < xml.len() { 'm' } else { 'l' };
+                        format!("{more}{}", &xml[start..end])
+                    }
+                    None => ERROR.into(),
+                }
+            }
+            // Everything else is unsupported, which GDB expects to be an empty reply
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+}
+
+fn read_register(gba: &Gba, n: usize) -> Option<u32> {
+    let regs = &gba.cpu.regs;
+    Some(match n {
+        0..=14 => regs.get_reg_i(n as u32),
+        15 => gba.cpu.next_pc(),
+        16 => regs.get_reg(Reg::Cpsr),
+        _ => {
+            let &(_, mode, reg) = BANKED.get(n - 17)?;
+            regs.get_banked(mode, reg)
+        }
+    })
+}
+
+/// Returns false for unknown registers and status registers with an invalid mode
+fn write_register(gba: &mut Gba, n: usize, value: u32) -> bool {
+    let pc = gba.cpu.next_pc();
+    let regs = &mut gba.cpu.regs;
+    match n {
+        0..=14 => regs.set_reg_i(n as u32, value),
+        15 => gba.cpu.set_next_pc(&gba.bus, value),
+        16 if Mode::from_bits(value).is_some() => {
+            regs.set_reg(Reg::Cpsr, value);
+            // The pipeline is refilled in case the state changed between ARM and Thumb
+            gba.cpu.set_next_pc(&gba.bus, pc);
+        }
+        _ => match BANKED.get(n.wrapping_sub(17)) {
+            Some(&(_, mode, reg)) if reg != Reg::Spsr || Mode::from_bits(value).is_some() => {
+                regs.set_banked(mode, reg, value)
+            }
+            _ => return false,
+        },
+    }
+    true
+}
+
+fn target_xml() -> String {
+    let mut xml = String::from(concat!(
+        r#"<?xml version="1.0"?><!DOCTYPE target SYSTEM "gdb-target.dtd">"#,
+        r#"<target version="1.0"><architecture>armv4t</architecture>"#,
+        r#"<feature name="org.gnu.gdb.arm.core">"#,
+    ));
+    for n in 0..13 {
+        write!(xml, r#"<reg name="r{n}" bitsize="32" type="uint32"/>"#).unwrap();
+    }
+    xml.push_str(concat!(
+        r#"<reg name="sp" bitsize="32" type="data_ptr"/>"#,
+        r#"<reg name="lr" bitsize="32"/>"#,
+        r#"<reg name="pc" bitsize="32" type="code_ptr"/>"#,
+        r#"<reg name="cpsr" bitsize="32"/>"#,
+        r#"</feature><feature name="org.fluorite.gba.banked">"#,
+    ));
+    for (name, _, _) in BANKED {
+        write!(xml, r#"<reg name="{name}" bitsize="32" group="banked"/>"#).unwrap();
+    }
+    xml.push_str("</feature></target>");
+    xml
+}
+
+fn hex_u32(value: u32) -> String {
+    value
+        .to_le_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn parse_bytes(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Registers are sent as little endian bytes
+fn parse_u32(hex: &str) -> Option<u32> {
+    Some(u32::from_le_bytes(parse_bytes(hex)?.try_into().ok()?))
+}
+
+/// `addr,len` in hex
+fn parse_range(args: &str) -> Option<(u32, u32)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((
+        u32::from_str_radix(addr, 16).ok()?,
+        u32::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+/// `type,addr,kind` in hex, where kind is the length for watchpoints
+fn parse_breakpoint(args: &str) -> Option<(u32, u32, u32)> {
+    let (kind, range) = args.split_once(',')?;
+    let (addr, len) = parse_range(range.split(';').next()?)?;
+    Some((u32::from_str_radix(kind, 16).ok()?, addr, len))
+}
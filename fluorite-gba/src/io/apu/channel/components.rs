@@ -1,3 +1,4 @@
+use crate::state::{impl_save_state, SaveState, StateReader, StateWriter};
 use num::{NumAssign, Unsigned};
 
 pub struct Sweep {
@@ -204,3 +205,38 @@ impl<T: NumAssign + Unsigned + Copy> Timer<T> {
         self.counter = reload;
     }
 }
+
+impl_save_state!(Sweep {
+    shift,
+    negate,
+    period,
+    enabled,
+    timer,
+    freq,
+    freq_shadow,
+    freq_overflowed,
+});
+impl_save_state!(LengthCounter { length });
+impl_save_state!(Envelope {
+    step_period,
+    inc,
+    initial_volume,
+    cur_volume,
+    timer,
+    active,
+});
+
+impl<T: NumAssign + Unsigned + Copy + SaveState> SaveState for Timer<T> {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.counter.save_state(w)
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        self.counter.load_state(r);
+        // A zero counter would underflow on the next clock
+        if self.counter == num::zero() {
+            r.invalidate();
+            self.counter = num::one();
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use super::Channel;
+use crate::state::impl_save_state;
 use std::collections::VecDeque;
 
 pub struct DMASound {
@@ -10,6 +11,14 @@ pub struct DMASound {
     sample: i16,
 }
 
+impl_save_state!(DMASound {
+    enable_right,
+    enable_left,
+    timer_select,
+    fifo,
+    sample,
+});
+
 impl DMASound {
     pub fn new() -> Self {
         Self {
@@ -1,5 +1,6 @@
 use super::components::*;
 use super::Channel;
+use crate::state::impl_save_state;
 
 pub struct Noise {
     // Registers
@@ -15,6 +16,18 @@ pub struct Noise {
     lfsr: u16,
 }
 
+impl_save_state!(Noise {
+    length_reload,
+    envelope,
+    ratio,
+    counter_width,
+    shift,
+    use_length,
+    length_counter,
+    timer,
+    lfsr,
+});
+
 impl Noise {
     pub fn new() -> Self {
         Self {
@@ -1,5 +1,6 @@
 use super::components::*;
 use super::Channel;
+use crate::state::impl_save_state;
 
 pub struct Tone {
     // Registers
@@ -15,6 +16,17 @@ pub struct Tone {
     duty_pos: usize,
 }
 
+impl_save_state!(Tone {
+    sweep,
+    _length_reload,
+    duty,
+    envelope,
+    use_length,
+    length_counter,
+    timer,
+    duty_pos,
+});
+
 impl Tone {
     // Filled with 8s so that wave ram volume can be more easily changed
     const DUTY: [[i16; 8]; 4] = [
@@ -1,5 +1,6 @@
 use super::components::*;
 use super::Channel;
+use crate::state::impl_save_state;
 
 pub struct Wave {
     // Registers
@@ -18,6 +19,21 @@ pub struct Wave {
     timer: Timer<u16>,
 }
 
+impl_save_state!(Wave {
+    use_two_banks,
+    wave_ram_bank,
+    enabled,
+    length_reload,
+    volume,
+    force_volume,
+    sample_rate,
+    use_length,
+    length_counter,
+    wave_ram,
+    wave_ram_i,
+    timer,
+});
+
 impl Wave {
     const VOLUME_FACTORS: [i16; 4] = [0, 8, 4, 2];
 
@@ -4,7 +4,7 @@ mod registers;
 use channel::*;
 use registers::*;
 
-use crate::{consts::CLOCK_FREQ, gba::AUDIO_DEVICE};
+use crate::{consts::CLOCK_FREQ, gba::AUDIO_DEVICE, state::impl_save_state};
 
 pub struct Apu {
     // Channels
@@ -25,6 +25,21 @@ pub struct Apu {
     fifo_b_req: bool,
 }
 
+impl_save_state!(Apu {
+    tone1,
+    tone2,
+    wave,
+    noise,
+    sound_a,
+    sound_b,
+    cnt,
+    bias,
+    master_enable,
+    sample_clock,
+    fifo_a_req,
+    fifo_b_req,
+});
+
 impl Apu {
     const CLOCKS_PER_SAMPLE: usize = CLOCK_FREQ / 0x8000;
 
@@ -49,6 +64,11 @@ impl Apu {
         }
     }
 
+    /// The BIOS centers the output before starting the cartridge
+    pub fn skip_bios(&mut self) {
+        self.bias.bias_level = 0x200;
+    }
+
     pub fn clock(&mut self) {
         if !self.master_enable {
             return;
@@ -1,3 +1,5 @@
+use crate::state::impl_save_state;
+
 pub struct SoundEnableFlags {
     pub channel1: u8,
     pub channel2: u8,
@@ -87,7 +89,7 @@ pub struct SoundBias {
 impl SoundBias {
     pub fn new() -> Self {
         Self {
-            bias_level: 0x200,
+            bias_level: 0,
             amplitude_res: 0,
         }
     }
@@ -111,3 +113,23 @@ impl SoundBias {
         }
     }
 }
+
+impl_save_state!(SoundEnableFlags {
+    channel1,
+    channel2,
+    channel3,
+    channel4
+});
+impl_save_state!(SoundCnt {
+    psg_master_volume_r,
+    psg_master_volume_l,
+    psg_enable_r,
+    psg_enable_l,
+    psg_volume,
+    dma_sound_a_vol,
+    dma_sound_b_vol,
+});
+impl_save_state!(SoundBias {
+    bias_level,
+    amplitude_res
+});
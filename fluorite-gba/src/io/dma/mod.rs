@@ -1,4 +1,5 @@
 use self::registers::{Address, DmaCnt, WordCount};
+use crate::state::impl_save_state;
 
 mod registers;
 
@@ -7,6 +8,8 @@ pub struct Dma {
     pub in_dma: bool,
 }
 
+impl_save_state!(Dma { channels, in_dma });
+
 impl Dma {
     pub fn new() -> Self {
         Self {
@@ -48,6 +51,17 @@ pub struct DmaChannel {
     pub cnt: DmaCnt,
 }
 
+impl_save_state!(DmaChannel {
+    num,
+    sad_latch,
+    dad_latch,
+    count_latch,
+    sad,
+    dad,
+    count,
+    cnt,
+});
+
 impl DmaChannel {
     const FIFO_A_ADDR: u32 = 0x40000A0;
     const FIFO_B_ADDR: u32 = 0x40000A4;
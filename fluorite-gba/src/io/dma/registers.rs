@@ -1,3 +1,5 @@
+use crate::state::impl_save_state;
+
 pub struct Address {
     pub addr: u32,
     byte3_mask: u32,
@@ -121,3 +123,17 @@ impl DmaCnt {
         }
     }
 }
+
+impl_save_state!(Address { addr, byte3_mask });
+impl_save_state!(WordCount { count, max });
+impl_save_state!(DmaCnt {
+    dest_addr_ctrl,
+    src_addr_ctrl,
+    repeat,
+    transfer_32,
+    game_pak_drq,
+    start_timing,
+    irq,
+    enable,
+    is_dma3,
+});
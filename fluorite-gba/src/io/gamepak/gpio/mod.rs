@@ -1,3 +1,4 @@
+use crate::state::{SaveState, StateReader, StateWriter};
 use enum_dispatch::enum_dispatch;
 use rtc::Rtc;
 
@@ -54,6 +55,20 @@ impl Gpio {
     }
 }
 
+impl SaveState for Gpio {
+    fn save_state(&self, w: &mut StateWriter) {
+        match self {
+            Gpio::Rtc(rtc) => rtc.save_state(w),
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        match self {
+            Gpio::Rtc(rtc) => rtc.load_state(r),
+        }
+    }
+}
+
 impl Default for Gpio {
     fn default() -> Self {
         Self::Rtc(unsafe { std::mem::zeroed() })
@@ -1,7 +1,10 @@
 use chrono::{Datelike, Timelike};
 
 use super::GpioDevice;
-use crate::consts::CLOCK_FREQ;
+use crate::{
+    consts::CLOCK_FREQ,
+    state::{impl_save_state, SaveState, StateReader, StateWriter},
+};
 
 pub struct Rtc {
     // Pins
@@ -20,6 +23,20 @@ pub struct Rtc {
     date_time: DateTime,
 }
 
+impl_save_state!(Rtc {
+    prev_sck,
+    sck,
+    sio,
+    cs,
+    is_used,
+    write_only,
+    write_mask,
+    mode,
+    last_byte,
+    counter,
+    date_time,
+});
+
 impl Rtc {
     const IDENTIFIER_STRING: &'static [u8] = "SIIRTC_V".as_bytes();
     const COMMAND_CODE: u8 = 0b0110;
@@ -329,6 +346,107 @@ enum Parameter {
     Irq,
 }
 
+impl SaveState for Mode {
+    fn save_state(&self, w: &mut StateWriter) {
+        match *self {
+            Mode::Start { done } => {
+                0u8.save_state(w);
+                done.save_state(w);
+            }
+            Mode::Set(byte, bit) => {
+                1u8.save_state(w);
+                byte.save_state(w);
+                bit.save_state(w);
+            }
+            Mode::Exec(parameter, access_type) => {
+                2u8.save_state(w);
+                parameter.save_state(w);
+                access_type.save_state(w);
+            }
+            Mode::End => 3u8.save_state(w),
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        let mut tag = 0u8;
+        tag.load_state(r);
+        *self = match tag {
+            0 => {
+                let mut done = false;
+                done.load_state(r);
+                Mode::Start { done }
+            }
+            1 => {
+                let (mut byte, mut bit) = (0u8, 0usize);
+                byte.load_state(r);
+                bit.load_state(r);
+                Mode::Set(byte, bit)
+            }
+            2 => {
+                let mut parameter = Parameter::Reset;
+                let mut access_type = AccessType::Read(0, 0);
+                parameter.load_state(r);
+                access_type.load_state(r);
+                Mode::Exec(parameter, access_type)
+            }
+            3 => Mode::End,
+            _ => return r.invalidate(),
+        };
+    }
+}
+
+impl SaveState for AccessType {
+    fn save_state(&self, w: &mut StateWriter) {
+        let (tag, byte, bit) = match *self {
+            AccessType::Read(byte, bit) => (0u8, byte, bit),
+            AccessType::Write(byte, bit) => (1u8, byte, bit),
+        };
+        tag.save_state(w);
+        byte.save_state(w);
+        bit.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        let (mut tag, mut byte, mut bit) = (0u8, 0u8, 0usize);
+        tag.load_state(r);
+        byte.load_state(r);
+        bit.load_state(r);
+        *self = match tag {
+            0 => AccessType::Read(byte, bit),
+            1 => AccessType::Write(byte, bit),
+            _ => return r.invalidate(),
+        };
+    }
+}
+
+impl SaveState for Parameter {
+    fn save_state(&self, w: &mut StateWriter) {
+        let (tag, byte) = match *self {
+            Parameter::Control(byte) => (0u8, byte),
+            Parameter::DateTime(byte) => (1u8, byte),
+            Parameter::Time(byte) => (2u8, byte),
+            Parameter::Reset => (3u8, 0),
+            Parameter::Irq => (4u8, 0),
+        };
+        tag.save_state(w);
+        byte.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        let (mut tag, mut byte) = (0u8, 0u8);
+        tag.load_state(r);
+        byte.load_state(r);
+        *self = match tag {
+            0 => Parameter::Control(byte),
+            1 => Parameter::DateTime(byte),
+            2 => Parameter::Time(byte),
+            3 => Parameter::Reset,
+            4 => Parameter::Irq,
+            _ => return r.invalidate(),
+        };
+    }
+}
+
 impl Parameter {
     pub fn from(value: u8) -> Self {
         match value {
@@ -347,6 +465,11 @@ struct Control {
     per_min_irq: bool,
 }
 
+impl_save_state!(Control {
+    is_24h,
+    per_min_irq
+});
+
 impl Control {
     pub fn new() -> Control {
         Control {
@@ -379,6 +502,18 @@ struct DateTime {
     second: Bcd,
 }
 
+impl_save_state!(DateTime {
+    control,
+    year,
+    month,
+    day,
+    day_of_week,
+    is_pm,
+    hour,
+    minute,
+    second,
+});
+
 impl DateTime {
     pub fn new() -> DateTime {
         let t = chrono::Local::now();
@@ -458,6 +593,12 @@ struct Bcd {
     max: u8,
 }
 
+impl_save_state!(Bcd {
+    initial,
+    value,
+    max
+});
+
 impl Bcd {
     pub fn new(initial: u8, max: u8) -> Self {
         Self {
@@ -2,8 +2,13 @@ use self::{
     gpio::{Gpio, GpioDevice},
     save::{SaveDevice, Saves},
 };
+use crate::state::impl_save_state;
 use rom::Rom;
-use std::path::Path;
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
 
 pub mod gpio;
 mod rom;
@@ -13,35 +18,125 @@ pub struct Gamepak {
     pub rom: Rom,
     pub gpio: Gpio,
     save: Saves,
+    // Flushing
+    persist: bool,
+    save_interval: usize,
+    frames_since_flush: usize,
 }
 
+// The rom itself isn't saved, states are tied to it through the header instead
+impl_save_state!(Gamepak { gpio, save });
+
 impl Gamepak {
     pub fn new() -> Self {
         Self {
             rom: Rom::default(),
             gpio: Gpio::default(),
             save: Saves::default(),
+            persist: true,
+            save_interval: 60,
+            frames_since_flush: 0,
+        }
+    }
+
+    /// Whether the backup is kept in a save file next to the rom. While off nothing is
+    /// written to disk and roms loaded afterwards start with an empty backup, which is
+    /// what tests and tools want.
+    pub fn set_persist(&mut self, persist: bool) {
+        self.persist = persist;
+    }
+
+    /// Sets how many frames pass between writes of a dirty backup to disk. 0 disables
+    /// periodic flushing, the backup is then only written on rom switch and on drop.
+    pub fn set_save_interval(&mut self, frames: usize) {
+        self.save_interval = frames;
+        self.frames_since_flush = 0;
+    }
+
+    pub fn frame_finished(&mut self) {
+        if self.save_interval == 0 {
+            return;
+        }
+
+        self.frames_since_flush += 1;
+        if self.frames_since_flush >= self.save_interval {
+            self.frames_since_flush = 0;
+            if let Err(err) = self.flush_save() {
+                warn!("Failed to write save: {err}");
+            }
+        }
+    }
+
+    /// Writes the backup to its save file if it changed since the last flush.
+    /// The data goes to a temporary file first and is renamed over the old save,
+    /// so a crash midway never leaves a truncated save behind. A failed write leaves
+    /// the backup dirty, so the next flush tries again.
+    pub fn flush_save(&mut self) -> io::Result<()> {
+        if !self.persist || !self.save.is_dirty() {
+            return Ok(());
         }
+        let path = self.save.get_save_file();
+        if path.as_os_str().is_empty() {
+            return Ok(());
+        }
+
+        Self::write_atomic(path, self.save.get_mem())?;
+        self.save.mark_clean();
+        Ok(())
+    }
+
+    fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(tmp_path, path)
     }
 
-    pub fn load(&mut self, rom: Option<&Path>, save: Option<&Path>) {
+    /// Loads a rom and/or a save file. A save is imported into the backup device of
+    /// the current rom, which keeps writing to its own save file afterwards.
+    pub fn load(&mut self, rom: Option<&Path>, save: Option<&Path>) -> io::Result<()> {
         assert!(rom.is_some() || save.is_some());
 
         if let Some(path) = rom {
-            self.rom.load(path).unwrap();
-            let mut path = path.to_path_buf();
-            path.set_extension(".sav");
+            if let Err(err) = self.flush_save() {
+                warn!("Failed to write save: {err}");
+            }
+            self.rom.load(path)?;
+            // An empty path means there is no save file
+            let path = if self.persist {
+                path.with_extension("sav")
+            } else {
+                PathBuf::new()
+            };
+            self.frames_since_flush = 0;
             self.save = Saves::new(self.rom.as_ref(), path);
             self.gpio = Gpio::new(self.rom.as_ref());
         }
 
-        if let Some(_path) = save {
-            todo!()
+        if let Some(path) = save {
+            let data = fs::read(path)?;
+            self.save.set_mem(&data);
         }
+
+        Ok(())
+    }
+
+    /// Writes the current backup contents to `path`
+    pub fn export_save(&self, path: &Path) -> io::Result<()> {
+        Self::write_atomic(path, self.save.get_mem())
     }
 
-    pub fn is_eeprom_access(&self, _addr: u32) -> bool {
-        todo!()
+    pub fn is_eeprom_access(&self, addr: u32) -> bool {
+        // Carts larger than 16MB only map the EEPROM to the top 256 bytes of ROM2
+        self.is_eeprom() && (self.rom.len() <= 0x0100_0000 || addr >= 0x0DFF_FF00)
+    }
+
+    pub fn init_eeprom(&mut self, dma_count: u32) {
+        if let Saves::Eeprom(eeprom) = &mut self.save {
+            eeprom.detect_size(dma_count)
+        }
     }
 
     pub fn read_save(&self, addr: u32) -> u8 {
@@ -65,6 +160,15 @@ impl Gamepak {
         match self.save {
             Saves::Sram(_) => false,
             Saves::Flash(_) => false,
+            Saves::Eeprom(_) => true,
+        }
+    }
+}
+
+impl Drop for Gamepak {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush_save() {
+            warn!("Failed to write save: {err}");
         }
     }
 }
@@ -1,3 +1,4 @@
+use crate::state;
 use std::{io, mem::size_of, path::Path};
 
 const MAX_SIZE: usize = 32 * 1024 * 1024;
@@ -8,24 +9,20 @@ pub struct Rom {
     pub mask: usize,
     pub code: String,
     pub title: String,
+    pub hash: u64,
 }
 
 impl Rom {
     pub fn load(&mut self, path: &Path) -> io::Result<()> {
-        println!("Loading rom: {path:?}");
+        info!("Loading rom {path:?}");
         let data = std::fs::read(path)?;
-        let size = dbg!(data.len());
-
-        // unsafe { data.set_len(data.capacity()) };
+        let size = data.len();
+        debug!("Rom size: {size} bytes");
 
         if size <= size_of::<Header>() || size > MAX_SIZE {
             panic!("Invalid ROM size: {size} bytes");
         }
 
-        // for addr in size..data.capacity() {
-        //     self.data[addr] = (((addr >> 1) >> (8 * (addr & 0x1))) & 0xFF) as u8
-        // }
-
         let header = Header::new(&data);
 
         if header.fixed_96h == 0x96 && header.complement == header.calc_complement() {
@@ -33,9 +30,9 @@ impl Rom {
             self.code = String::from_utf8(header.game_code.to_vec()).unwrap();
         }
 
-        println!("Title: {}", self.title);
-        println!("Code: {}", self.code);
+        info!("Title: {}, code: {}", self.title, self.code);
 
+        self.hash = state::hash(&data);
         self.data = data;
 
         Ok(())
@@ -0,0 +1,287 @@
+use super::{SaveDevice, Saves};
+use crate::state::{SaveState, StateReader, StateWriter};
+use std::{cell::Cell, fs, path::PathBuf};
+
+pub struct Eeprom {
+    data: Box<[u8]>,
+    save_file: PathBuf,
+    is_dirty: bool,
+
+    // Bus width is unknown until the size has been detected
+    addr_bits: Option<usize>,
+    // Serial transfer
+    mode: Cell<Mode>,
+    buffer: u64,
+    bits_received: usize,
+}
+
+impl Eeprom {
+    const SIZE_4K: usize = 0x200;
+    const SIZE_64K: usize = 0x2000;
+
+    // Lengths of the DMA transfers used for read and write requests
+    const READ_REQUEST_4K: u32 = 2 + 6 + 1;
+    const READ_REQUEST_64K: u32 = 2 + 14 + 1;
+    const WRITE_REQUEST_4K: u32 = 2 + 6 + 64 + 1;
+    const WRITE_REQUEST_64K: u32 = 2 + 14 + 64 + 1;
+
+    pub fn new(save_file: PathBuf) -> Eeprom {
+        let (data, addr_bits) = match fs::read(&save_file) {
+            Ok(data) if data.len() == Self::SIZE_4K => (data.into_boxed_slice(), Some(6)),
+            Ok(data) if data.len() == Self::SIZE_64K => (data.into_boxed_slice(), Some(14)),
+            _ => (vec![0xFF; Self::SIZE_64K].into_boxed_slice(), None),
+        };
+
+        Eeprom {
+            data,
+            save_file,
+            is_dirty: false,
+
+            addr_bits,
+            mode: Cell::new(Mode::Command),
+            buffer: 0,
+            bits_received: 0,
+        }
+    }
+
+    /// Detects the chip size from the length of a DMA transfer to the EEPROM.
+    /// Games always use DMA for requests, so the first one tells us the bus width.
+    pub fn detect_size(&mut self, dma_count: u32) {
+        if self.addr_bits.is_some() {
+            return;
+        }
+
+        self.addr_bits = match dma_count {
+            Self::READ_REQUEST_4K | Self::WRITE_REQUEST_4K => Some(6),
+            Self::READ_REQUEST_64K | Self::WRITE_REQUEST_64K => Some(14),
+            _ => return,
+        };
+        if self.addr_bits == Some(6) {
+            self.data = self.data[..Self::SIZE_4K].into();
+        }
+    }
+
+    fn reset_transfer(&mut self) {
+        self.mode.set(Mode::Command);
+        self.buffer = 0;
+        self.bits_received = 0;
+    }
+
+    fn addr_bits(&self) -> usize {
+        self.addr_bits.unwrap_or(14)
+    }
+
+    fn block_addr(&self, addr: u64) -> usize {
+        // Only the lower 10 bits of the 64Kbit address are connected
+        (addr as usize & 0x3FF) * 8 % self.data.len()
+    }
+
+    // Shifts in a bit and returns true once `len` bits have been received
+    fn receive_bit(&mut self, bit: u8, len: usize) -> bool {
+        self.buffer = self.buffer << 1 | bit as u64;
+        self.bits_received += 1;
+        if self.bits_received == len {
+            self.bits_received = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl SaveDevice for Eeprom {
+    fn read(&self, _addr: u32) -> u8 {
+        match self.mode.get() {
+            Mode::Read { addr, bit } => {
+                self.mode.set(if bit + 1 == 68 {
+                    Mode::Command
+                } else {
+                    Mode::Read { addr, bit: bit + 1 }
+                });
+                // The first 4 bits are ignored
+                if bit < 4 {
+                    0
+                } else {
+                    let bit = bit - 4;
+                    self.data[addr + bit / 8] >> (7 - bit % 8) & 0x1
+                }
+            }
+            // Writes complete immediately, so the chip always reports ready
+            _ => 1,
+        }
+    }
+
+    fn write(&mut self, offset: u32, value: u8) {
+        let bit = value & 0x1;
+        match self.mode.get() {
+            Mode::Command => {
+                if self.receive_bit(bit, 2) {
+                    match self.buffer & 0x3 {
+                        0b11 => self.mode.set(Mode::ReadAddr),
+                        0b10 => self.mode.set(Mode::WriteAddr),
+                        _ => (),
+                    }
+                    self.buffer = 0;
+                }
+            }
+            Mode::ReadAddr => {
+                if self.receive_bit(bit, self.addr_bits()) {
+                    let addr = self.block_addr(self.buffer);
+                    self.buffer = 0;
+                    self.mode.set(Mode::ReadEnd { addr });
+                }
+            }
+            Mode::ReadEnd { addr } => self.mode.set(Mode::Read { addr, bit: 0 }),
+            Mode::WriteAddr => {
+                if self.receive_bit(bit, self.addr_bits()) {
+                    let addr = self.block_addr(self.buffer);
+                    self.buffer = 0;
+                    self.mode.set(Mode::WriteData { addr });
+                }
+            }
+            Mode::WriteData { addr } => {
+                if self.receive_bit(bit, 64) {
+                    self.data[addr..addr + 8].copy_from_slice(&self.buffer.to_be_bytes());
+                    self.is_dirty = true;
+                    self.buffer = 0;
+                    self.mode.set(Mode::WriteEnd);
+                }
+            }
+            Mode::WriteEnd => self.mode.set(Mode::Command),
+            // A new request aborts an unfinished read
+            Mode::Read { .. } => {
+                self.mode.set(Mode::Command);
+                self.write(offset, value);
+            }
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.is_dirty
+    }
+
+    fn mark_clean(&mut self) {
+        self.is_dirty = false;
+    }
+
+    fn get_save_file(&self) -> &PathBuf {
+        &self.save_file
+    }
+
+    fn get_mem(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn set_mem(&mut self, data: &[u8]) {
+        // A bus width detected from the game wins, otherwise the file tells the size
+        if self.addr_bits.is_none() {
+            self.addr_bits = match data.len() {
+                Self::SIZE_4K => Some(6),
+                Self::SIZE_64K => Some(14),
+                _ => None,
+            };
+        }
+        let size = match self.addr_bits {
+            Some(6) => Self::SIZE_4K,
+            _ => Self::SIZE_64K,
+        };
+        self.data = if data.len() == size {
+            data.into()
+        } else {
+            Saves::resize_data(data, 0xFF, size)
+        };
+        self.reset_transfer();
+        self.is_dirty = true;
+    }
+}
+
+impl SaveState for Eeprom {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.addr_bits.unwrap_or(0).save_state(w);
+        self.data.to_vec().save_state(w);
+        self.mode.get().save_state(w);
+        self.buffer.save_state(w);
+        self.bits_received.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        let mut addr_bits = 0usize;
+        let mut data = Vec::new();
+        let mut mode = Mode::Command;
+        addr_bits.load_state(r);
+        data.load_state(r);
+        mode.load_state(r);
+        self.buffer.load_state(r);
+        self.bits_received.load_state(r);
+
+        let valid_size = matches!(
+            (addr_bits, data.len()),
+            (0 | 14, Self::SIZE_64K) | (6, Self::SIZE_4K)
+        );
+        if !valid_size || mode.block_addr() + 8 > data.len() {
+            return r.invalidate();
+        }
+        self.addr_bits = if addr_bits == 0 { None } else { Some(addr_bits) };
+        self.data = data.into_boxed_slice();
+        self.mode.set(mode);
+        // The backup on disk no longer matches the loaded memory
+        self.is_dirty = true;
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Command,
+    ReadAddr,
+    ReadEnd { addr: usize },
+    Read { addr: usize, bit: usize },
+    WriteAddr,
+    WriteData { addr: usize },
+    WriteEnd,
+}
+
+impl Mode {
+    fn block_addr(&self) -> usize {
+        match *self {
+            Mode::ReadEnd { addr } | Mode::Read { addr, .. } | Mode::WriteData { addr } => addr,
+            _ => 0,
+        }
+    }
+}
+
+impl SaveState for Mode {
+    fn save_state(&self, w: &mut StateWriter) {
+        let (tag, addr, bit) = match *self {
+            Mode::Command => (0u8, 0, 0),
+            Mode::ReadAddr => (1, 0, 0),
+            Mode::ReadEnd { addr } => (2, addr, 0),
+            Mode::Read { addr, bit } => (3, addr, bit),
+            Mode::WriteAddr => (4, 0, 0),
+            Mode::WriteData { addr } => (5, addr, 0),
+            Mode::WriteEnd => (6, 0, 0),
+        };
+        tag.save_state(w);
+        addr.save_state(w);
+        bit.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        let (mut tag, mut addr, mut bit) = (0u8, 0usize, 0usize);
+        tag.load_state(r);
+        addr.load_state(r);
+        bit.load_state(r);
+        if bit >= 68 {
+            return r.invalidate();
+        }
+        *self = match tag {
+            0 => Mode::Command,
+            1 => Mode::ReadAddr,
+            2 => Mode::ReadEnd { addr },
+            3 => Mode::Read { addr, bit },
+            4 => Mode::WriteAddr,
+            5 => Mode::WriteData { addr },
+            6 => Mode::WriteEnd,
+            _ => return r.invalidate(),
+        };
+    }
+}
@@ -1,4 +1,5 @@
 use super::{SaveDevice, Saves};
+use crate::state::{impl_save_state_enum, SaveState, StateReader, StateWriter};
 use std::path::PathBuf;
 
 pub struct Flash {
@@ -121,10 +122,12 @@ impl SaveDevice for Flash {
         };
     }
 
-    fn is_dirty(&mut self) -> bool {
-        let is_dirty = self.is_dirty;
+    fn is_dirty(&self) -> bool {
+        self.is_dirty
+    }
+
+    fn mark_clean(&mut self) {
         self.is_dirty = false;
-        is_dirty
     }
 
     fn get_save_file(&self) -> &PathBuf {
@@ -134,19 +137,66 @@ impl SaveDevice for Flash {
     fn get_mem(&self) -> &[u8] {
         &self.data
     }
+
+    fn set_mem(&mut self, data: &[u8]) {
+        // The bank switching of the detected chip has to stay in bounds
+        self.data = if data.len() == self.data.len() {
+            data.into()
+        } else {
+            Saves::resize_data(data, 0xFF, self.data.len())
+        };
+        self.command = Command::Command0;
+        self.mode = Mode::Ready;
+        self.bank = 0;
+        self.in_chip_ident = false;
+        self.is_dirty = true;
+    }
+}
+
+impl SaveState for Flash {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.data.save_state(w);
+        self.command.save_state(w);
+        self.mode.save_state(w);
+        self.bank.save_state(w);
+        self.in_chip_ident.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        self.data.load_state(r);
+        self.command.load_state(r);
+        self.mode.load_state(r);
+        self.bank.load_state(r);
+        self.in_chip_ident.load_state(r);
+        // The backup on disk no longer matches the loaded memory
+        self.is_dirty = true;
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Command {
     Command0,
     Command1,
     Command2,
 }
 
-#[derive(Debug, PartialEq)]
+impl_save_state_enum!(Command {
+    Command0,
+    Command1,
+    Command2
+});
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Mode {
     Ready,
     Erase,
     Write,
     SetBank,
 }
+
+impl_save_state_enum!(Mode {
+    Ready,
+    Erase,
+    Write,
+    SetBank
+});
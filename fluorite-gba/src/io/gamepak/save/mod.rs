@@ -2,9 +2,13 @@ use std::{fs, path::PathBuf};
 
 use enum_dispatch::enum_dispatch;
 
+use crate::state::{SaveState, StateReader, StateWriter};
+
+use self::eeprom::Eeprom;
 use self::flash::Flash;
 use self::sram::Sram;
 
+mod eeprom;
 mod flash;
 mod sram;
 
@@ -35,23 +39,58 @@ pub trait SaveDevice {
     fn read(&self, addr: u32) -> u8;
     fn write(&mut self, addr: u32, value: u8);
 
-    #[allow(clippy::wrong_self_convention)]
-    fn is_dirty(&mut self) -> bool;
+    /// Whether the backup changed since it was last written to the save file
+    fn is_dirty(&self) -> bool;
+    fn mark_clean(&mut self);
     fn get_save_file(&self) -> &PathBuf;
     fn get_mem(&self) -> &[u8];
+    /// Replaces the backup contents, e.g. with a save file picked by the user
+    fn set_mem(&mut self, data: &[u8]);
 }
 
 #[enum_dispatch]
 pub enum Saves {
     Sram,
     Flash,
+    Eeprom,
+}
+
+impl SaveState for Saves {
+    fn save_state(&self, w: &mut StateWriter) {
+        match self {
+            Saves::Sram(sram) => {
+                0u8.save_state(w);
+                sram.save_state(w);
+            }
+            Saves::Flash(flash) => {
+                1u8.save_state(w);
+                flash.save_state(w);
+            }
+            Saves::Eeprom(eeprom) => {
+                2u8.save_state(w);
+                eeprom.save_state(w);
+            }
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        let mut tag = 0u8;
+        tag.load_state(r);
+        // The save type is detected from the rom, which the header has already matched
+        match (tag, self) {
+            (0, Saves::Sram(sram)) => sram.load_state(r),
+            (1, Saves::Flash(flash)) => flash.load_state(r),
+            (2, Saves::Eeprom(eeprom)) => eeprom.load_state(r),
+            _ => r.invalidate(),
+        }
+    }
 }
 
 impl Default for Saves {
     fn default() -> Self {
         Self::Sram(Sram {
             data: Box::new([]),
-            _save_file: "".into(),
+            save_file: "".into(),
             is_dirty: false,
         })
     }
@@ -69,7 +108,7 @@ impl Saves {
     pub fn new(rom: &[u8], save_file: PathBuf) -> Self {
         if let Some(save_type) = Self::get_type(rom) {
             match save_type {
-                SaveType::Eeprom => todo!(),
+                SaveType::Eeprom => Eeprom::new(save_file).into(),
                 SaveType::Sram => Sram::new(save_file).into(),
                 SaveType::Flash => Flash::new(save_file, 0x10000).into(),
                 SaveType::Flash512 => Flash::new(save_file, 0x10000).into(),
@@ -98,6 +137,20 @@ impl Saves {
         ty
     }
 
+    // Used when an imported save doesn't match the size of the detected chip.
+    // The data is truncated or padded so the game at least sees its start.
+    fn resize_data(data: &[u8], default_val: u8, size: usize) -> Box<[u8]> {
+        warn!(
+            "Save file is {:#X} bytes but the backup is {:#X} bytes, resizing",
+            data.len(),
+            size
+        );
+        let mut resized = vec![default_val; size];
+        let len = data.len().min(size);
+        resized[..len].copy_from_slice(&data[..len]);
+        resized.into_boxed_slice()
+    }
+
     fn get_initial_data(save_file: &PathBuf, default_val: u8, size: usize) -> Box<[u8]> {
         if let Ok(data) = fs::read(save_file) {
             if data.len() == size {
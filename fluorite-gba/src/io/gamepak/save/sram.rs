@@ -1,9 +1,10 @@
 use super::{SaveDevice, Saves};
+use crate::state::{SaveState, StateReader, StateWriter};
 use std::path::PathBuf;
 
 pub struct Sram {
     pub(super) data: Box<[u8]>,
-    pub(super) _save_file: PathBuf,
+    pub(super) save_file: PathBuf,
     pub(super) is_dirty: bool,
 }
 
@@ -13,39 +14,57 @@ impl Sram {
     pub fn new(save_file: PathBuf) -> Self {
         Self {
             data: Saves::get_initial_data(&save_file, 0, Self::SIZE),
-            _save_file: save_file,
+            save_file,
             is_dirty: false,
         }
     }
 }
 
+impl SaveState for Sram {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.data.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        self.data.load_state(r);
+        // The backup on disk no longer matches the loaded memory
+        self.is_dirty = true;
+    }
+}
+
 impl SaveDevice for Sram {
+    // The 32KB are mirrored in the upper half of the 64KB backup region
     fn read(&self, addr: u32) -> u8 {
-        let addr = addr as usize;
-        if addr < Self::SIZE {
-            self.data[addr]
-        } else {
-            0
-        }
+        self.data[addr as usize % Self::SIZE]
     }
 
     fn write(&mut self, addr: u32, value: u8) {
-        let addr = addr as usize;
-        if addr < Self::SIZE {
-            self.is_dirty = true;
-            self.data[addr] = value
-        }
+        self.is_dirty = true;
+        self.data[addr as usize % Self::SIZE] = value
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.is_dirty
     }
 
-    fn is_dirty(&mut self) -> bool {
-        todo!()
+    fn mark_clean(&mut self) {
+        self.is_dirty = false;
     }
 
     fn get_save_file(&self) -> &PathBuf {
-        todo!()
+        &self.save_file
     }
 
     fn get_mem(&self) -> &[u8] {
-        todo!()
+        &self.data
+    }
+
+    fn set_mem(&mut self, data: &[u8]) {
+        self.data = if data.len() == Self::SIZE {
+            data.into()
+        } else {
+            Saves::resize_data(data, 0, Self::SIZE)
+        };
+        self.is_dirty = true;
     }
 }
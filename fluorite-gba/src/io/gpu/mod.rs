@@ -3,6 +3,7 @@ use super::interrupt_controller::InterruptRequest;
 use crate::{
     consts::{HEIGHT, WIDTH},
     gba::Pixels,
+    state::impl_save_state,
 };
 
 mod registers;
@@ -63,6 +64,44 @@ pub struct Gpu {
     pub pixels: Pixels,
 }
 
+// The line buffers are rebuilt every scanline, so they aren't part of the state
+impl_save_state!(Gpu {
+    dispcnt,
+    green_swap,
+    dispstat,
+    vcount,
+    bgcnts,
+    hofs,
+    vofs,
+    dxs,
+    dmxs,
+    dys,
+    dmys,
+    bgxs,
+    bgys,
+    bgxs_latch,
+    bgys_latch,
+    mosaic,
+    winhs,
+    winvs,
+    win_0_cnt,
+    win_1_cnt,
+    win_out_cnt,
+    win_obj_cnt,
+    bldcnt,
+    bldalpha,
+    bldy,
+    bg_palettes,
+    obj_palettes,
+    vram,
+    oam,
+    hblank_called,
+    vblank_called,
+    rendered_frame,
+    dot,
+    pixels,
+});
+
 impl Gpu {
     const TRANSPARENT_COLOR: u16 = 0x8000;
     const OBJ_SIZES: [[(i16, u16); 3]; 4] = [
@@ -82,10 +121,10 @@ impl Gpu {
             bgcnts: [BgCnt::new(); 4],
             hofs: [Ofs::new(); 4],
             vofs: [Ofs::new(); 4],
-            dxs: [RotationScalingParameter::new(); 2],
+            dxs: [RotationScalingParameter::one(); 2],
             dmxs: [RotationScalingParameter::new(); 2],
             dys: [RotationScalingParameter::new(); 2],
-            dmys: [RotationScalingParameter::new(); 2],
+            dmys: [RotationScalingParameter::one(); 2],
             bgxs: [ReferencePointCoord::new(); 2],
             bgys: [ReferencePointCoord::new(); 2],
             bgxs_latch: [ReferencePointCoord::new(); 2],
@@ -301,14 +340,38 @@ impl Gpu {
             0x035 => self.dys[1].write::<1>(val),
             0x036 => self.dmys[1].write::<0>(val),
             0x037 => self.dmys[1].write::<1>(val),
-            0x038 => self.bgxs[1].write::<0>(val),
-            0x039 => self.bgxs[1].write::<1>(val),
-            0x03A => self.bgxs[1].write::<2>(val),
-            0x03B => self.bgxs[1].write::<3>(val),
-            0x03C => self.bgys[1].write::<0>(val),
-            0x03D => self.bgys[1].write::<1>(val),
-            0x03E => self.bgys[1].write::<2>(val),
-            0x03F => self.bgys[1].write::<3>(val),
+            0x038 => {
+                self.bgxs[1].write::<0>(val);
+                self.bgxs_latch[1] = self.bgxs[1]
+            }
+            0x039 => {
+                self.bgxs[1].write::<1>(val);
+                self.bgxs_latch[1] = self.bgxs[1]
+            }
+            0x03A => {
+                self.bgxs[1].write::<2>(val);
+                self.bgxs_latch[1] = self.bgxs[1]
+            }
+            0x03B => {
+                self.bgxs[1].write::<3>(val);
+                self.bgxs_latch[1] = self.bgxs[1]
+            }
+            0x03C => {
+                self.bgys[1].write::<0>(val);
+                self.bgys_latch[1] = self.bgys[1]
+            }
+            0x03D => {
+                self.bgys[1].write::<1>(val);
+                self.bgys_latch[1] = self.bgys[1]
+            }
+            0x03E => {
+                self.bgys[1].write::<2>(val);
+                self.bgys_latch[1] = self.bgys[1]
+            }
+            0x03F => {
+                self.bgys[1].write::<3>(val);
+                self.bgys_latch[1] = self.bgys[1]
+            }
             0x040 => self.winhs[0].write::<0>(val),
             0x041 => self.winhs[0].write::<1>(val),
             0x042 => self.winhs[1].write::<0>(val),
@@ -369,6 +432,17 @@ impl Gpu {
         vblank_called
     }
 
+    /// Dots to emulate until one that can request an interrupt or start a DMA,
+    /// including that dot
+    pub fn dots_until_event(&self) -> usize {
+        // HBlank starts, HDMA starts, the line ends and VBlank starts on the first dot
+        let event = [0, 240, 250, 307]
+            .into_iter()
+            .find(|&dot| dot >= self.dot)
+            .unwrap();
+        (event - self.dot) as usize + 1
+    }
+
     pub fn emulate_dot(&mut self) -> InterruptRequest {
         let mut interrupts = InterruptRequest::new();
 
@@ -484,48 +558,40 @@ impl Gpu {
                 });
                 self.process_lines(0, 2);
             }
-            BGMode::Mode2 => todo!(),
-            BGMode::Mode3 => {
-                let (mosaic_x, mosaic_y) = if self.bgcnts[2].mosaic {
-                    (
-                        self.mosaic.bg_size.h_size as usize,
-                        self.mosaic.bg_size.v_size,
-                    )
-                } else {
-                    (1, 1)
-                };
-                for dot_x in 0..WIDTH {
-                    let y = self.vcount / mosaic_y * mosaic_y;
-                    let x = dot_x / mosaic_x * mosaic_x;
-                    let addr = (y as usize * WIDTH + x) * 2;
-                    self.bg_lines[2][dot_x] =
-                        u16::from_le_bytes([self.vram[addr], self.vram[addr + 1]]);
+            BGMode::Mode2 => {
+                let mut bgs = vec![];
+                if self.dispcnt.display_bg2() {
+                    bgs.push(2)
+                }
+                if self.dispcnt.display_bg3() {
+                    bgs.push(3)
                 }
+
+                bgs.into_iter().for_each(|bg_i| self.render_affine_line(bg_i));
+                self.process_lines(2, 3);
+            }
+            BGMode::Mode3 => {
+                self.render_bitmap_line(WIDTH, HEIGHT, true, 0);
                 self.process_lines(2, 2);
             }
-
             BGMode::Mode4 => {
-                let (mosaic_x, mosaic_y) = if self.bgcnts[2].mosaic {
-                    (
-                        self.mosaic.bg_size.h_size as usize,
-                        self.mosaic.bg_size.v_size,
-                    )
+                let frame_addr = if self.dispcnt.display_frame_select() {
+                    0xA000
                 } else {
-                    (1, 1)
+                    0
                 };
-                let y = self.vcount / mosaic_y * mosaic_y;
-                let start_addr = if self.dispcnt.display_frame_select() {
+                self.render_bitmap_line(WIDTH, HEIGHT, false, frame_addr);
+                self.process_lines(2, 2);
+            }
+            BGMode::Mode5 => {
+                let frame_addr = if self.dispcnt.display_frame_select() {
                     0xA000
                 } else {
                     0
-                } + y as usize * WIDTH;
-                for dot_x in 0..WIDTH {
-                    let x = dot_x / mosaic_x * mosaic_x;
-                    self.bg_lines[2][dot_x] = self.bg_palettes[self.vram[start_addr + x] as usize];
-                }
+                };
+                self.render_bitmap_line(160, 128, true, frame_addr);
                 self.process_lines(2, 2);
             }
-            BGMode::Mode5 => todo!(),
         }
     }
 
@@ -881,11 +947,11 @@ impl Gpu {
             base_x += dx;
             base_y += dy;
             let (x, y) =
-                if x_raw < 0 || x_raw > map_size as i32 || y_raw < 0 || y_raw > map_size as i32 {
+                if x_raw < 0 || x_raw >= map_size as i32 || y_raw < 0 || y_raw >= map_size as i32 {
                     if bgcnt.wrap {
                         (
-                            (x_raw % map_size as i32) as usize,
-                            (y_raw % map_size as i32) as usize,
+                            x_raw.rem_euclid(map_size as i32) as usize,
+                            y_raw.rem_euclid(map_size as i32) as usize,
                         )
                     } else {
                         self.bg_lines[bg_i][dot_x] = Self::TRANSPARENT_COLOR;
@@ -919,6 +985,49 @@ impl Gpu {
         }
     }
 
+    // Bitmap modes sample BG2 through the affine engine. Unlike the tiled affine
+    // backgrounds they never wrap, anything outside of the bitmap is transparent.
+    fn render_bitmap_line(&mut self, width: usize, height: usize, bpp16: bool, frame_addr: usize) {
+        let mut base_x = self.bgxs_latch[0];
+        let mut base_y = self.bgys_latch[0];
+        self.bgxs_latch[0] += self.dmxs[0];
+        self.bgys_latch[0] += self.dmys[0];
+        let dx = self.dxs[0];
+        let dy = self.dys[0];
+        let (mosaic_x, mosaic_y) = if self.bgcnts[2].mosaic {
+            (
+                self.mosaic.bg_size.h_size as usize,
+                self.mosaic.bg_size.v_size as usize,
+            )
+        } else {
+            (1, 1)
+        };
+
+        for dot_x in 0..WIDTH {
+            let (x_raw, y_raw) = (base_x.integer(), base_y.integer());
+            base_x += dx;
+            base_y += dy;
+            if x_raw < 0 || x_raw >= width as i32 || y_raw < 0 || y_raw >= height as i32 {
+                self.bg_lines[2][dot_x] = Self::TRANSPARENT_COLOR;
+                continue;
+            }
+            let x = x_raw as usize / mosaic_x * mosaic_x;
+            let y = y_raw as usize / mosaic_y * mosaic_y;
+
+            self.bg_lines[2][dot_x] = if bpp16 {
+                let addr = frame_addr + (y * width + x) * 2;
+                u16::from_le_bytes([self.vram[addr], self.vram[addr + 1]]) & 0x7FFF
+            } else {
+                let color_num = self.vram[frame_addr + y * width + x] as usize;
+                if color_num == 0 {
+                    Self::TRANSPARENT_COLOR
+                } else {
+                    self.bg_palettes[color_num]
+                }
+            };
+        }
+    }
+
     fn render_text_line(&mut self, bg_i: usize) {
         let x_offset = self.hofs[bg_i].0 as usize;
         let y_offset = self.vofs[bg_i].0 as usize;
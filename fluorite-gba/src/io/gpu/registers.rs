@@ -1,3 +1,4 @@
+use crate::state::{impl_save_state, impl_save_state_enum};
 use fluorite_common::bitfield;
 use std::ops::{Deref, DerefMut};
 
@@ -550,6 +551,11 @@ impl RotationScalingParameter {
         Self(0)
     }
 
+    /// 1.0 in 8.8 fixed point, which is what the BIOS initializes PA and PD to
+    pub fn one() -> Self {
+        Self(0x100)
+    }
+
     pub fn get_float_from_u16(value: u16) -> f64 {
         (value >> 8) as i8 as i32 as f64 + value as u8 as f64 / 256.0
     }
@@ -599,3 +605,61 @@ impl WindowDimensions {
         }
     }
 }
+
+impl_save_state_enum!(BGMode {
+    Mode0,
+    Mode1,
+    Mode2,
+    Mode3,
+    Mode4,
+    Mode5
+});
+impl_save_state!(DISPCNTFlags { 0 });
+impl_save_state!(Dispcnt { flags, mode });
+impl_save_state!(DISPSTATFlags { 0 });
+impl_save_state!(Dispstat {
+    flags,
+    vcount_setting
+});
+impl_save_state!(BgCnt {
+    priority,
+    tile_block,
+    mosaic,
+    bpp8,
+    map_block,
+    wrap,
+    screen_size,
+});
+impl_save_state!(MosaicSize { h_size, v_size });
+impl_save_state!(Mosaic { bg_size, obj_size });
+impl_save_state!(BldCntTargetPixelSelection { enabled });
+impl_save_state_enum!(ColorSFX {
+    None,
+    AlphaBlend,
+    _BrightnessInc,
+    _BrightnessDec
+});
+impl_save_state!(BldCnt {
+    target_pixel1,
+    effect,
+    target_pixel2
+});
+impl_save_state!(WindowControl {
+    bg0_enable,
+    bg1_enable,
+    bg2_enable,
+    bg3_enable,
+    obj_enable,
+    color_special_enable,
+});
+impl_save_state!(BldAlpha {
+    _raw_eva,
+    _raw_evb,
+    eva,
+    evb
+});
+impl_save_state!(Bldy { evy });
+impl_save_state!(Ofs { 0 });
+impl_save_state!(ReferencePointCoord { 0 });
+impl_save_state!(RotationScalingParameter { 0 });
+impl_save_state!(WindowDimensions { coord2, coord1 });
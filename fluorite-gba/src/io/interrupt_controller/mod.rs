@@ -2,12 +2,20 @@ mod registers;
 
 pub use registers::*;
 
+use crate::state::impl_save_state;
+
 pub struct InterruptController {
     pub enable: InterruptEnable,
     pub master_enable: InterruptMasterEnable,
     pub request: InterruptRequest,
 }
 
+impl_save_state!(InterruptController {
+    enable,
+    master_enable,
+    request
+});
+
 impl InterruptController {
     pub fn new() -> InterruptController {
         InterruptController {
@@ -1,3 +1,4 @@
+use crate::state::impl_save_state;
 use fluorite_common::bitfield;
 use std::ops::BitOrAssign;
 
@@ -11,12 +12,12 @@ bitfield! {
         pub timer1_overflow: bool @ 4,
         pub timer2_overflow: bool @ 5,
         pub timer3_overflow: bool @ 6,
-        serial: bool @ 7,
+        pub serial: bool @ 7,
         pub dma0: bool @ 8,
         pub dma1: bool @ 9,
         pub dma2: bool @ 10,
         pub dma3: bool @ 11,
-        keypad: bool @ 12,
+        pub keypad: bool @ 12,
         gamepak: bool @ 13,
     }
 }
@@ -82,12 +83,12 @@ bitfield! {
         pub timer1_overflow: bool @ 4,
         pub timer2_overflow: bool @ 5,
         pub timer3_overflow: bool @ 6,
-        serial: bool @ 7,
+        pub serial: bool @ 7,
         pub dma0: bool @ 8,
         pub dma1: bool @ 9,
         pub dma2: bool @ 10,
         pub dma3: bool @ 11,
-        keypad: bool @ 12,
+        pub keypad: bool @ 12,
         gamepak: bool @ 13,
     }
 }
@@ -119,3 +120,7 @@ impl BitOrAssign for InterruptRequest {
         self.0 |= rhs.0
     }
 }
+
+impl_save_state!(InterruptEnable { 0 });
+impl_save_state!(InterruptMasterEnable { 0 });
+impl_save_state!(InterruptRequest { 0 });
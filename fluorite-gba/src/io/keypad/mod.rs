@@ -1,3 +1,5 @@
+use super::interrupt_controller::InterruptRequest;
+use crate::state::impl_save_state;
 use fluorite_common::{bitfield, flume::Receiver};
 
 bitfield! {
@@ -64,34 +66,94 @@ impl KEYCNT {
     }
 }
 
+bitfield! {
+    /// The keys held down. Unlike in KEYINPUT a set bit means the key is pressed.
+    #[derive(Clone, Copy, Default, PartialEq, Eq)]
+    pub struct KeyState(pub u16) {
+        pub a: bool @ 0,
+        pub b: bool @ 1,
+        pub select: bool @ 2,
+        pub start: bool @ 3,
+        pub right: bool @ 4,
+        pub left: bool @ 5,
+        pub up: bool @ 6,
+        pub down: bool @ 7,
+        pub r: bool @ 8,
+        pub l: bool @ 9,
+    }
+}
+
+impl_save_state!(KEYINPUT { 0 });
+impl_save_state!(KEYCNT { 0 });
+
 pub struct Keypad {
     pub keyinput: KEYINPUT,
     pub keycnt: KEYCNT,
-    rx: Receiver<(u16, bool)>,
+    rx: Option<Receiver<(u16, bool)>>,
 }
 
+impl_save_state!(Keypad { keyinput, keycnt });
+
 impl Keypad {
-    pub fn new(rx: Receiver<(u16, bool)>) -> Self {
+    pub fn new() -> Self {
         Self {
-            keyinput: KEYINPUT(0x1FF),
+            keyinput: KEYINPUT(0x3FF),
             keycnt: KEYCNT(0),
-            rx,
+            rx: None,
         }
     }
 
     pub fn reset(&mut self) {
-        self.keyinput = KEYINPUT(0x1FF);
+        self.keyinput = KEYINPUT(0x3FF);
         self.keycnt = KEYCNT(0);
     }
 
-    pub fn poll(&mut self) {
-        for (key, pressed) in self.rx.try_iter() {
-            if pressed {
-                self.keyinput.0 &= !key;
-            } else {
-                self.keyinput.0 |= key;
+    /// Key events sent through `rx` are applied once per frame by `poll`
+    pub fn set_receiver(&mut self, rx: Receiver<(u16, bool)>) {
+        self.rx = Some(rx)
+    }
+
+    pub fn poll(&mut self) -> InterruptRequest {
+        let keyinput = self.keyinput.0;
+        if let Some(rx) = &self.rx {
+            for (key, pressed) in rx.try_iter() {
+                if pressed {
+                    self.keyinput.0 &= !key;
+                } else {
+                    self.keyinput.0 |= key;
+                }
             }
         }
+        self.keys_changed(keyinput)
+    }
+
+    pub fn keys(&self) -> KeyState {
+        KeyState(!self.keyinput.0 & 0x3FF)
+    }
+
+    pub fn set_keys(&mut self, keys: KeyState) -> InterruptRequest {
+        let keyinput = self.keyinput.0;
+        self.keyinput.0 = !keys.0 & 0x3FF;
+        self.keys_changed(keyinput)
+    }
+
+    pub fn write_keycnt(&mut self, byte: u8, value: u8) -> InterruptRequest {
+        self.keycnt.write(byte, value);
+        self.update_interrupt()
+    }
+
+    // Like on hardware the condition is checked whenever KEYINPUT changes or KEYCNT is
+    // written, so holding the keys down doesn't request the interrupt again on its own
+    fn keys_changed(&self, keyinput: u16) -> InterruptRequest {
+        if keyinput == self.keyinput.0 {
+            InterruptRequest::new()
+        } else {
+            self.update_interrupt()
+        }
+    }
+
+    fn update_interrupt(&self) -> InterruptRequest {
+        InterruptRequest::new().with_keypad(self.interrupt_requested())
     }
 
     pub fn interrupt_requested(&self) -> bool {
@@ -0,0 +1,129 @@
+use crate::state::impl_save_state;
+use std::fmt;
+
+/// Severity of a message printed through the mGBA debug port
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugLevel {
+    Fatal,
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl DebugLevel {
+    fn new(flags: u16) -> Option<Self> {
+        use DebugLevel::*;
+        match flags & 0x7 {
+            0 => Some(Fatal),
+            1 => Some(Error),
+            2 => Some(Warn),
+            3 => Some(Info),
+            4 => Some(Debug),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DebugLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DebugLevel::Fatal => "FATAL",
+            DebugLevel::Error => "ERROR",
+            DebugLevel::Warn => "WARN",
+            DebugLevel::Info => "INFO",
+            DebugLevel::Debug => "DEBUG",
+        };
+        f.pad(name)
+    }
+}
+
+pub type DebugSink = Box<dyn FnMut(DebugLevel, &str) + Send>;
+
+/// The debug port of mGBA. Once 0xC0DE is written to 0x4FFF780 a rom can write a string
+/// to 0x4FFF600 and print it by writing its level with bit 8 set to 0x4FFF700.
+pub struct MgbaDebug {
+    buffer: [u8; 0x100],
+    // Registers
+    enable: u16,
+    flags: u16,
+
+    sink: Option<DebugSink>,
+}
+
+impl_save_state!(MgbaDebug {
+    buffer,
+    enable,
+    flags
+});
+
+impl MgbaDebug {
+    pub fn new() -> Self {
+        Self {
+            buffer: [0; 0x100],
+            enable: 0,
+            flags: 0,
+            sink: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer = [0; 0x100];
+        self.enable = 0;
+        self.flags = 0;
+    }
+
+    /// Messages go to the `log` crate until a sink is set
+    pub fn set_sink(&mut self, sink: DebugSink) {
+        self.sink = Some(sink)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enable == 0xC0DE
+    }
+
+    pub fn read_register(&self, addr: u32) -> u8 {
+        match addr {
+            0x4FFF780 if self.enabled() => 0xEA,
+            0x4FFF781 if self.enabled() => 0x1D,
+            _ => 0,
+        }
+    }
+
+    pub fn write_register(&mut self, addr: u32, value: u8) {
+        match addr {
+            0x4FFF780 => self.enable = self.enable & !0x00FF | value as u16,
+            0x4FFF781 => self.enable = self.enable & !0xFF00 | (value as u16) << 8,
+            _ if !self.enabled() => (),
+            0x4FFF600..=0x4FFF6FF => self.buffer[(addr - 0x4FFF600) as usize] = value,
+            0x4FFF700 => self.flags = self.flags & !0x00FF | value as u16,
+            0x4FFF701 => {
+                self.flags = self.flags & !0xFF00 | (value as u16) << 8;
+                if self.flags & 0x100 != 0 {
+                    self.send();
+                    self.flags &= !0x100;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn send(&mut self) {
+        let len = self.buffer.iter().position(|&c| c == 0).unwrap_or(0x100);
+        let message = String::from_utf8_lossy(&self.buffer[..len]);
+
+        // Like mGBA, messages with an invalid level are dropped
+        if let Some(level) = DebugLevel::new(self.flags) {
+            match &mut self.sink {
+                Some(sink) => sink(level, &message),
+                None => match level {
+                    DebugLevel::Fatal | DebugLevel::Error => error!("{message}"),
+                    DebugLevel::Warn => warn!("{message}"),
+                    DebugLevel::Info => info!("{message}"),
+                    DebugLevel::Debug => debug!("{message}"),
+                },
+            }
+        }
+        self.buffer = [0; 0x100];
+    }
+}
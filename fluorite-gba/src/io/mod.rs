@@ -7,15 +7,23 @@ use self::{
     },
     gpu::Gpu,
     interrupt_controller::InterruptController,
-    keypad::Keypad,
+    keypad::{KeyState, Keypad},
     memory::{MemoryRegion, MemoryValue},
+    mgba_debug::{DebugSink, MgbaDebug},
     scheduler::{Event, EventType, Scheduler},
+    sio::{LinkBackend, Sio},
     timers::Timers,
 };
-use crate::{consts::CLOCK_FREQ, io::interrupt_controller::InterruptRequest, BIOS};
+use crate::{
+    bios::Bios,
+    consts::CLOCK_FREQ,
+    debugger::{WatchKind, Watchpoints},
+    io::interrupt_controller::InterruptRequest,
+    state::{impl_save_state, impl_save_state_enum},
+};
 use fluorite_common::flume::Receiver;
 use num::FromPrimitive;
-use std::{cell::Cell, collections::VecDeque, mem::size_of};
+use std::{collections::VecDeque, mem::size_of};
 
 pub mod apu;
 pub mod dma;
@@ -24,7 +32,9 @@ pub mod gpu;
 pub mod interrupt_controller;
 pub mod keypad;
 pub mod memory;
+pub mod mgba_debug;
 pub mod scheduler;
+pub mod sio;
 pub mod timers;
 
 #[derive(Clone, Copy)]
@@ -32,6 +42,22 @@ pub enum MemoryAccess {
     N,
     S,
 }
+
+impl_save_state_enum!(MemoryAccess { N, S });
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum HaltMode {
+    Running,
+    Halted,
+    Stopped,
+}
+
+impl_save_state_enum!(HaltMode {
+    Running,
+    Halted,
+    Stopped
+});
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Cycle {
     N,
@@ -52,6 +78,7 @@ pub struct Sysbus {
     // pub rom: Box<[u8]>,
     pub gamepak: Gamepak,
 
+    bios: Bios,
     ewram: Box<[u8]>,
     iwram: Box<[u8]>,
 
@@ -63,32 +90,60 @@ pub struct Sysbus {
     apu: Apu,
     dma: Dma,
     timers: Timers,
+    sio: Sio,
     keypad: Keypad,
     interrupt_controller: InterruptController,
     _rtc: (),
     _backup: (),
 
     // registers
-    haltcnt: u16,
+    postflg: u8,
+    halt_mode: HaltMode,
     waitcnt: WaitStateControl,
 
     // open bus
     pc: u32,
     in_thumb: bool,
     pipeline: [u32; 2],
-    bios_latch: Cell<u32>,
 
-    mgba_test_suite: mgba_test_suite::MGBATestSuite,
+    mgba_debug: MgbaDebug,
+
+    // Debugging, not part of save states
+    pub(crate) watchpoints: Watchpoints,
 }
 
+impl_save_state!(Sysbus {
+    gamepak,
+    ewram,
+    iwram,
+    scheduler,
+    clocks_ahead,
+    gpu,
+    apu,
+    dma,
+    timers,
+    sio,
+    keypad,
+    interrupt_controller,
+    postflg,
+    halt_mode,
+    waitcnt,
+    pc,
+    in_thumb,
+    pipeline,
+    bios,
+    mgba_debug,
+});
+
 impl Sysbus {
     const EWRAM_MASK: u32 = 0x3FFFF;
     const IWRAM_MASK: u32 = 0x7FFF;
 
-    pub fn new(rx: Receiver<(u16, bool)>) -> Self {
+    pub fn new(bios: Bios) -> Self {
         Self {
             gamepak: Gamepak::new(),
 
+            bios,
             ewram: vec![0; 0x40000].into_boxed_slice(),
             iwram: vec![0; 0x8000].into_boxed_slice(),
 
@@ -99,20 +154,23 @@ impl Sysbus {
             apu: Apu::new(),
             dma: Dma::new(),
             timers: Timers::new(),
-            keypad: Keypad::new(rx),
+            sio: Sio::new(),
+            keypad: Keypad::new(),
             interrupt_controller: InterruptController::new(),
             _rtc: (),
             _backup: (),
 
-            haltcnt: 0,
+            postflg: 0,
+            halt_mode: HaltMode::Running,
             waitcnt: WaitStateControl::new(),
 
             pc: 0,
             in_thumb: false,
             pipeline: [0; 2],
-            bios_latch: Cell::new(0xE129F000),
 
-            mgba_test_suite: mgba_test_suite::MGBATestSuite::new(),
+            mgba_debug: MgbaDebug::new(),
+
+            watchpoints: Watchpoints::new(),
         }
     }
 
@@ -125,19 +183,42 @@ impl Sysbus {
         self.apu = Apu::new();
         self.dma = Dma::new();
         self.timers = Timers::new();
+        self.sio.reset(&mut self.scheduler);
         self.keypad.reset();
         self.interrupt_controller = InterruptController::new();
         self._rtc = ();
         self._backup = ();
-        self.haltcnt = 0;
+        self.postflg = 0;
+        self.halt_mode = HaltMode::Running;
         self.waitcnt = WaitStateControl::new();
         self.pc = 0;
         self.in_thumb = false;
         self.pipeline = [0; 2];
-        self.bios_latch.set(0xE129F000);
+        self.bios.reset();
+        self.mgba_debug.reset();
+    }
+
+    /// Sets up the registers the BIOS leaves behind when it starts the cartridge
+    pub fn skip_bios(&mut self) {
+        self.postflg = 1;
+        self.apu.skip_bios();
     }
 
     pub fn read<T>(&self, addr: u32) -> T
+    where
+        T: MemoryValue,
+    {
+        // Without watchpoints this is a well predicted branch on the length of an empty
+        // list, which doesn't show up in the fps bench. The check itself is kept out of line.
+        if self.watchpoints.is_active() {
+            self.watchpoints
+                .check(addr, size_of::<T>() as u32, WatchKind::Read);
+        }
+        self.fetch(addr)
+    }
+
+    /// Reads like `read`, but isn't caught by watchpoints. Used for instruction fetches.
+    pub fn fetch<T>(&self, addr: u32) -> T
     where
         T: MemoryValue,
     {
@@ -167,7 +248,13 @@ impl Sysbus {
             MemoryRegion::Rom1L => self.read_rom(addr),
             MemoryRegion::Rom1H => self.read_rom(addr),
             MemoryRegion::Rom2L => self.read_rom(addr),
-            MemoryRegion::Rom2H => todo!(),
+            MemoryRegion::Rom2H => {
+                if self.gamepak.is_eeprom_access(addr) {
+                    FromPrimitive::from_u8(self.read_cart_backup(addr)).unwrap()
+                } else {
+                    self.read_rom(addr)
+                }
+            }
             MemoryRegion::Sram => self.read_sram(addr),
             MemoryRegion::Unused => self.read_openbus(addr),
         }
@@ -177,6 +264,10 @@ impl Sysbus {
     where
         T: MemoryValue,
     {
+        if self.watchpoints.is_active() {
+            self.watchpoints
+                .check(addr, size_of::<T>() as u32, WatchKind::Write);
+        }
         match MemoryRegion::get_region(addr) {
             MemoryRegion::Bios => (),
             MemoryRegion::Ewram => Self::write_mem(&mut self.ewram, addr & Self::EWRAM_MASK, value),
@@ -195,16 +286,78 @@ impl Sysbus {
                     )
                 }
             }
-            MemoryRegion::Rom0H => todo!(),
-            MemoryRegion::Rom1L => todo!(),
-            MemoryRegion::Rom1H => todo!(),
-            MemoryRegion::Rom2L => todo!(),
-            MemoryRegion::Rom2H => todo!(),
+            // The cartridge ignores writes to the rom
+            MemoryRegion::Rom0H => (),
+            MemoryRegion::Rom1L => (),
+            MemoryRegion::Rom1H => (),
+            MemoryRegion::Rom2L => (),
+            MemoryRegion::Rom2H => {
+                if self.gamepak.is_eeprom_access(addr) {
+                    self.write_cart_backup(addr, num::cast::<T, u8>(value & num::one()).unwrap())
+                }
+            }
             MemoryRegion::Sram => self.write_sram(addr, value),
             MemoryRegion::Unused => {}
         }
     }
 
+    /// Reads a byte for a debugger. Unlike `read` this doesn't update the BIOS latch, talk
+    /// to the EEPROM or empty the UART receive buffer, and it takes no time.
+    pub fn debug_read8(&self, addr: u32) -> u8 {
+        match MemoryRegion::get_region(addr) {
+            MemoryRegion::Bios => self.bios.peek(addr),
+            MemoryRegion::Io => self.debug_read_io_register(addr),
+            MemoryRegion::Rom2H => self.read_rom(addr),
+            _ => self.fetch(addr),
+        }
+    }
+
+    /// Like `debug_read8`, the bytes are read in little endian order as they are without
+    /// aligning `addr`
+    pub fn debug_read16(&self, addr: u32) -> u16 {
+        Self::read_from_bytes(self, &Self::debug_read8, addr)
+    }
+
+    pub fn debug_read32(&self, addr: u32) -> u32 {
+        Self::read_from_bytes(self, &Self::debug_read8, addr)
+    }
+
+    /// Writes a byte for a debugger. Memory is written as is, without the mirroring of
+    /// byte writes to VRAM and palette RAM, and the rom can be patched. IO registers are
+    /// written like the CPU does, except HALTCNT which would halt it.
+    pub fn debug_write8(&mut self, addr: u32, value: u8) {
+        match MemoryRegion::get_region(addr) {
+            MemoryRegion::Bios | MemoryRegion::Unused => (),
+            MemoryRegion::Ewram => self.ewram[(addr & Self::EWRAM_MASK) as usize] = value,
+            MemoryRegion::Iwram => self.iwram[(addr & Self::IWRAM_MASK) as usize] = value,
+            MemoryRegion::Io if addr == 0x04000301 => (),
+            MemoryRegion::Io => self.write_register(addr, value),
+            MemoryRegion::Palette => self.gpu.write_palette_ram(addr, value),
+            MemoryRegion::Vram => self.gpu.vram[Gpu::parse_vram_addr(addr) as usize] = value,
+            MemoryRegion::Oam => self.gpu.oam[Gpu::parse_oam_addr(addr) as usize] = value,
+            MemoryRegion::Rom0L
+            | MemoryRegion::Rom0H
+            | MemoryRegion::Rom1L
+            | MemoryRegion::Rom1H
+            | MemoryRegion::Rom2L
+            | MemoryRegion::Rom2H => {
+                let rom = &mut self.gamepak.rom.data;
+                if let Some(byte) = rom.get_mut((addr & 0x01FF_FFFF) as usize) {
+                    *byte = value;
+                }
+            }
+            MemoryRegion::Sram => self.write_sram(addr, value),
+        }
+    }
+
+    pub fn debug_write16(&mut self, addr: u32, value: u16) {
+        Self::write_from_bytes(self, &Self::debug_write8, addr, value)
+    }
+
+    pub fn debug_write32(&mut self, addr: u32, value: u32) {
+        Self::write_from_bytes(self, &Self::debug_write8, addr, value)
+    }
+
     pub fn inc_clock<C: Into<Cycle>>(&mut self, cycle: C, addr: u32, access_width: u32) {
         let cycle = cycle.into();
         let clocks_inc = if cycle == Cycle::I {
@@ -247,7 +400,10 @@ impl Sysbus {
             }
         };
         self.waitcnt.clock_prefetch(clocks_inc);
+        self.clock(clocks_inc);
+    }
 
+    fn clock(&mut self, clocks_inc: u32) {
         for _ in 0..clocks_inc {
             self.handle_events();
             // self.gamepak.gpio.clock();
@@ -293,27 +449,121 @@ impl Sysbus {
                     event_type: EventType::FrameSequencer((step + 1) % 8),
                 });
             }
+            EventType::SerialTransfer => {
+                self.interrupt_controller.request |= self.sio.clock_transfer(&mut self.scheduler)
+            }
+            EventType::SerialPoll => {
+                self.interrupt_controller.request |= self.sio.poll(&mut self.scheduler)
+            }
         }
     }
 
-    pub fn interrupts_requested(&mut self) -> bool {
-        // if self.keypad.interrupt_requested() {
-        //     self.interrupt_controller.request |= InterruptRequest::KEYPAD
-        // }
+    pub fn set_link(&mut self, link: Box<dyn LinkBackend>) {
+        self.sio.set_link(&mut self.scheduler, link)
+    }
+
+    /// The cable isn't part of a save state, polling has to match the one plugged in now
+    pub fn resume_link(&mut self) {
+        self.sio.schedule_poll(&mut self.scheduler)
+    }
 
+    pub fn interrupts_requested(&mut self) -> bool {
         self.interrupt_controller.master_enable.enabled()
             && (self.interrupt_controller.request.raw() & self.interrupt_controller.enable.raw())
                 != 0
     }
 
+    pub fn is_halted(&self) -> bool {
+        self.halt_mode != HaltMode::Running
+    }
+
+    /// Runs everything but the CPU until an interrupt wakes it up or `end_cycle` is reached
+    pub fn idle(&mut self, end_cycle: usize) {
+        // Only the keypad, serial and gamepak interrupts end stop mode
+        const STOP_WAKE_MASK: u16 = 0x3080;
+
+        while self.scheduler.cycle < end_cycle {
+            let pending =
+                self.interrupt_controller.request.raw() & self.interrupt_controller.enable.raw();
+            match self.halt_mode {
+                HaltMode::Running => return,
+                HaltMode::Halted if pending != 0 => self.halt_mode = HaltMode::Running,
+                HaltMode::Stopped if pending & STOP_WAKE_MASK != 0 => {
+                    self.halt_mode = HaltMode::Running
+                }
+                // DMA keeps running while the CPU is halted. Interrupts and DMA requests only
+                // come from scheduler events and a few dots of the GPU, so everything in
+                // between is clocked at once.
+                HaltMode::Halted => {
+                    self.run_dma();
+                    let gpu_event = self.scheduler.cycle + 4 * self.gpu.dots_until_event()
+                        - self.clocks_ahead as usize;
+                    let next_cycle = self
+                        .scheduler
+                        .next_event_cycle()
+                        .map_or(end_cycle, |cycle| cycle.min(end_cycle))
+                        .min(gpu_event);
+                    let clocks = (next_cycle - self.scheduler.cycle) as u32;
+                    self.waitcnt.clock_prefetch(clocks);
+                    self.clock(clocks);
+                }
+                // Video, sound, timers and DMA are off. Only serial transfers keep going,
+                // the timer and sound events are pushed back by the time spent stopped.
+                HaltMode::Stopped => {
+                    let next_cycle = self
+                        .scheduler
+                        .next_unclocked_event_cycle()
+                        .filter(|&cycle| cycle <= end_cycle);
+                    let clocks = next_cycle.unwrap_or(end_cycle) - self.scheduler.cycle;
+                    self.scheduler.postpone_clocked(clocks);
+                    self.timers.postpone(clocks);
+                    match next_cycle {
+                        Some(cycle) => {
+                            self.scheduler.cycle = cycle - 1;
+                            self.handle_events();
+                        }
+                        None => self.scheduler.cycle = end_cycle,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether SWIs have to be emulated because no BIOS dump was provided
+    pub fn is_hle_bios(&self) -> bool {
+        self.bios.is_hle()
+    }
+
+    /// Sets the value open bus reads of the BIOS return once the CPU has left it
+    pub fn set_bios_latch(&mut self, value: u32) {
+        self.bios.set_latch(value)
+    }
+
+    pub fn set_debug_sink(&mut self, sink: DebugSink) {
+        self.mgba_debug.set_sink(sink)
+    }
+
     pub fn get_cycle(&self) -> usize {
         self.scheduler.cycle
     }
 
+    pub fn keys(&self) -> KeyState {
+        self.keypad.keys()
+    }
+
+    pub fn set_keys(&mut self, keys: KeyState) {
+        self.interrupt_controller.request |= self.keypad.set_keys(keys);
+    }
+
+    pub fn set_key_receiver(&mut self, rx: Receiver<(u16, bool)>) {
+        self.keypad.set_receiver(rx)
+    }
+
     pub fn poll_keypad_updates(&mut self) {
-        if self.gpu.rendered_frame() {
-            // TODO: write save to file
-            self.keypad.poll();
+        // No frames are drawn in stop mode, but the keypad can still wake the CPU
+        if self.gpu.rendered_frame() || self.halt_mode == HaltMode::Stopped {
+            self.gamepak.frame_finished();
+            self.interrupt_controller.request |= self.keypad.poll();
         }
     }
 
@@ -352,12 +602,11 @@ impl Sysbus {
             //     if transfer_32 { 32 } else { 16 }
             // );
 
-            // TODO:
-            // if MemoryRegion::get_region(dest_addr) == MemoryRegion::ROM2H
-            //     && self.cart_backup.is_eeprom_access(dest_addr, self.rom.len())
-            // {
-            //     self.cart_backup.init_eeprom(count)
-            // }
+            if MemoryRegion::get_region(dest_addr) == MemoryRegion::Rom2H
+                && self.gamepak.is_eeprom_access(dest_addr)
+            {
+                self.gamepak.init_eeprom(count)
+            }
 
             let (access_width, addr_change, addr_mask) = if transfer_32 {
                 (2, 4, 0x3)
@@ -453,30 +702,29 @@ impl Sysbus {
     where
         T: MemoryValue,
     {
-        if self.pc < 0x4000 {
-            self.bios_latch.set(Self::read_mem(BIOS, addr)); // Always 32 bit read
-            Self::read_mem(BIOS, addr)
-        } else {
-            let mask = match size_of::<T>() {
-                1 => 0xFF,
-                2 => 0xFFFF,
-                4 => 0xFFFF_FFFF,
-                _ => unreachable!(),
-            };
-            FromPrimitive::from_u32((self.bios_latch.get() >> ((addr & 3) * 8)) & mask).unwrap()
-        }
+        self.bios.read(self.pc, addr)
     }
 
     fn read_rom<T>(&self, addr: u32) -> T
     where
         T: MemoryValue,
     {
-        let addr = addr - 0x08000000;
-        if (addr as usize) < self.gamepak.rom.len() {
-            Self::read_mem(self.gamepak.rom.as_ref(), addr)
-        } else {
-            num::zero()
+        let addr = addr & 0x01FF_FFFF;
+        if addr as usize + size_of::<T>() <= self.gamepak.rom.len() {
+            return Self::read_mem(self.gamepak.rom.as_ref(), addr);
         }
+
+        // Nothing drives the bus past the end of the rom, or without a cartridge, so the
+        // lower bits of the halfword address latched by the cartridge are read back
+        let aligned = addr & !0x3;
+        let value = (aligned >> 1) & 0xFFFF | ((aligned + 2) >> 1 & 0xFFFF) << 16;
+        let mask = match size_of::<T>() {
+            1 => 0xFF,
+            2 => 0xFFFF,
+            4 => 0xFFFF_FFFF,
+            _ => unreachable!(),
+        };
+        FromPrimitive::from_u32((value >> ((addr & 3) * 8)) & mask).unwrap()
     }
 
     fn read_sram<T>(&self, addr: u32) -> T
@@ -491,8 +739,9 @@ impl Sysbus {
                 _ => unreachable!(),
             };
         }
-        let addr = addr & 0x0EFFFFFF;
-        let byte = FromPrimitive::from_u8(self.read_cart_backup(addr - 0x0E000000)).unwrap();
+        // The backup only has an 8 bit bus, wider reads see the byte repeated. Its 64KB
+        // are mirrored across both SRAM regions.
+        let byte = FromPrimitive::from_u8(self.read_cart_backup(addr & 0xFFFF)).unwrap();
         match size_of::<T>() {
             1 => byte,
             2 => byte * FromPrimitive::from_u16(0x0101).unwrap(),
@@ -518,15 +767,12 @@ impl Sysbus {
             0x04000104..=0x04000107 => self.timers.timers[1].read(&self.scheduler, addr as u8 % 4),
             0x04000108..=0x0400010B => self.timers.timers[2].read(&self.scheduler, addr as u8 % 4),
             0x0400010C..=0x0400010F => self.timers.timers[3].read(&self.scheduler, addr as u8 % 4),
-            0x04000120..=0x0400012F => {
-                warn!("Read from SerialCom(1)");
-                0
-            }
+            0x04000120..=0x0400012F => self.sio.read(addr),
             0x04000130 => self.keypad.keyinput.read::<0>(),
             0x04000131 => self.keypad.keyinput.read::<1>(),
             0x04000132 => self.keypad.keycnt.read::<0>(),
             0x04000133 => self.keypad.keycnt.read::<1>(),
-            0x04000134..=0x04000159 => todo!(),
+            0x04000134..=0x04000159 => self.sio.read(addr),
             0x0400015A..=0x040001FF => 0,
             0x04000200 => self.interrupt_controller.enable.read::<0>(),
             0x04000201 => self.interrupt_controller.enable.read::<1>(),
@@ -538,14 +784,21 @@ impl Sysbus {
             0x04000208 => self.interrupt_controller.master_enable.read::<0>(),
             0x04000209 => self.interrupt_controller.master_enable.read::<1>(),
             0x0400020A..=0x040002FF => 0, // Unused IO Register
-            0x04000300 => self.haltcnt as u8,
-            0x04000301 => (self.haltcnt >> 8) as u8,
-            0x04FFF780..=0x04FFF781 => self.mgba_test_suite.read_register(addr),
+            0x04000300 => self.postflg,
+            0x04000301 => 0, // HALTCNT is write only
+            0x04FFF780..=0x04FFF781 => self.mgba_debug.read_register(addr),
             // 0x04000000..=0x04700000 => panic!("Reading Unimplemented IO Register at {addr:08X}"),
             _ => 0,
         }
     }
 
+    fn debug_read_io_register(&self, addr: u32) -> u8 {
+        match addr {
+            0x04000120..=0x0400012F | 0x04000134..=0x04000159 => self.sio.peek(addr),
+            _ => self.read_io_register(addr),
+        }
+    }
+
     fn read_openbus<T>(&self, addr: u32) -> T
     where
         T: MemoryValue,
@@ -612,10 +865,11 @@ impl Sysbus {
                 self.timers.timers[3].write(&mut self.scheduler, addr as u8 % 4, val)
             }
             0x04000110..=0x0400011F => (),
-            0x04000120..=0x0400012B => warn!("Writng SerialCom(1) at {addr:08X} = {val:02X}",), // TODO: serial communication(1)
-            0x0400012C..=0x0400012F => (),
-            0x04000130..=0x04000133 => warn!("Writng Keypad at {addr:08X} = {val:02X}",), // TODO: Keypad Input
-            0x04000134..=0x04000159 => warn!("Writng SerialCom(2) at {addr:08X} = {val:02X}",), // TODO: serial communication(2)
+            0x04000120..=0x0400012F => self.sio.write(&mut self.scheduler, addr, val),
+            0x04000130..=0x04000131 => (), // KEYINPUT is read only
+            0x04000132 => self.interrupt_controller.request |= self.keypad.write_keycnt(0, val),
+            0x04000133 => self.interrupt_controller.request |= self.keypad.write_keycnt(1, val),
+            0x04000134..=0x04000159 => self.sio.write(&mut self.scheduler, addr, val),
             0x0400015A..=0x040001FF => (),
             0x04000200 => self.interrupt_controller.enable.write::<0>(val),
             0x04000201 => self.interrupt_controller.enable.write::<1>(val),
@@ -627,11 +881,18 @@ impl Sysbus {
             0x04000208 => self.interrupt_controller.master_enable.write::<0>(val),
             0x04000209 => self.interrupt_controller.master_enable.write::<1>(val),
             0x0400020A..=0x040002FF => (), // Unused IO Register
-            0x04000300 => self.haltcnt = (self.haltcnt & !0x00FF) | val as u16,
-            0x04000301 => self.haltcnt = (self.haltcnt & !0xFF00) | (val as u16) << 8,
+            0x04000300 => self.postflg = val & 0x1,
+            0x04000301 => {
+                self.halt_mode = if val & 0x80 != 0 {
+                    HaltMode::Stopped
+                } else {
+                    HaltMode::Halted
+                }
+            }
             0x04000410 => (), // Undocumented
-            0x04FFF600..=0x04FFF701 => self.mgba_test_suite.write_register(addr, val),
-            0x04FFF780..=0x04FFF781 => self.mgba_test_suite.write_enable(addr, val),
+            0x04FFF600..=0x04FFF701 | 0x04FFF780..=0x04FFF781 => {
+                self.mgba_debug.write_register(addr, val)
+            }
             _ => (), //unreachable!("Writng Unimplemented IO Register at {addr:08X} = {val:02X}",),
         }
     }
@@ -676,11 +937,13 @@ impl Sysbus {
     where
         T: MemoryValue,
     {
-        // TODO: this should do nothing if eeprom exists
-        let addr = addr & 0x0EFFFFFF;
+        if self.gamepak.is_eeprom() {
+            return;
+        }
+        // Wider writes only store the byte of the value that lines up with the address
         let mask = FromPrimitive::from_u8(0xFF).unwrap();
         self.write_cart_backup(
-            addr - 0x0E000000,
+            addr & 0xFFFF,
             num::cast::<T, u8>(val.rotate_right(addr * 8) & mask).unwrap(),
         );
     }
@@ -705,6 +968,20 @@ struct WaitStateControl {
     prefetch_cycles_spent: u32,
 }
 
+impl_save_state!(WaitStateControl {
+    sram_setting,
+    n_wait_state_settings,
+    s_wait_state_settings,
+    phi_terminal_out,
+    use_prefetch,
+    _type_flag,
+    can_prefetch,
+    prefetch,
+    prefetch_waitstate,
+    prefetch_addr,
+    prefetch_cycles_spent,
+});
+
 impl WaitStateControl {
     const N_ACCESS_TIMINGS: [u32; 4] = [4, 3, 2, 8];
     const S_ACCESS_TIMINGS: [[u32; 2]; 3] = [[2, 1], [4, 1], [8, 1]];
@@ -834,131 +1111,3 @@ impl WaitStateControl {
         }
     }
 }
-
-mod mgba_test_suite {
-    enum MGBALogLevel {
-        Fatal,
-        Error,
-        Warn,
-        Info,
-        Debug,
-    }
-
-    impl MGBALogLevel {
-        pub fn new(val: u16) -> Self {
-            use MGBALogLevel::*;
-            match val {
-                0 => Fatal,
-                1 => Error,
-                2 => Warn,
-                3 => Info,
-                4 => Debug,
-                _ => panic!("Invalid mGBA Log Level!"),
-            }
-        }
-    }
-
-    pub struct MGBATestSuite {
-        buffer: [char; 0x100],
-        // Registers
-        enable: u16,
-        flags: u16,
-    }
-
-    impl MGBATestSuite {
-        pub fn new() -> MGBATestSuite {
-            MGBATestSuite {
-                buffer: ['\0'; 0x100],
-                enable: 0,
-                flags: 0,
-            }
-        }
-
-        pub fn enabled(&self) -> bool {
-            self.enable == 0xC0DE
-        }
-
-        pub fn write_enable(&mut self, addr: u32, value: u8) {
-            match addr {
-                0x4FFF780 => self.enable = self.enable & !0x00FF | (value as u16) & 0x00FF,
-                0x4FFF781 => self.enable = self.enable & !0xFF00 | (value as u16) << 8 & 0xFF00,
-                _ => (),
-            }
-        }
-
-        pub fn read_register(&self, addr: u32) -> u8 {
-            match addr {
-                0x4FFF780 => {
-                    if self.enabled() {
-                        0xEA
-                    } else {
-                        0
-                    }
-                }
-                0x4FFF781 => {
-                    if self.enabled() {
-                        0x1D
-                    } else {
-                        0
-                    }
-                }
-                _ => 0,
-            }
-        }
-
-        pub fn write_register(&mut self, addr: u32, value: u8) {
-            if !self.enabled() {
-                return;
-            }
-            match addr {
-                0x4FFF600..=0x4FFF6FF => self.buffer[(addr - 0x4FFF600) as usize] = value as char,
-                0x4FFF700 => self.flags = self.flags & !0x00FF | (value as u16) & 0x00FF,
-                0x4FFF701 => {
-                    self.flags = self.flags & !0xFF00 | (value as u16) << 8 & 0xFF00;
-                    if self.flags & 0x100 != 0 {
-                        use MGBALogLevel::*;
-                        let null_byte_pos = self
-                            .buffer
-                            .iter()
-                            .position(|&c| c == '\0')
-                            .unwrap_or(self.buffer.len());
-                        let message: String = self.buffer.iter().take(null_byte_pos).collect();
-
-                        if message.contains("PASS") {
-                            return;
-                        }
-                        let show_info = message.contains("FAIL")
-                            && !message
-                                .split(' ')
-                                .skip(1)
-                                .take(1)
-                                .collect::<String>()
-                                .contains('P');
-                        let show_debug = !message
-                            .split(' ')
-                            .rev()
-                            .take(1)
-                            .collect::<String>()
-                            .contains('P');
-                        match MGBALogLevel::new(self.flags & 0x7) {
-                            Fatal => error!("{message}"),
-                            Error => error!("{message}"),
-                            Warn => warn!("{message}"),
-                            Info => {
-                                if show_info {
-                                    info!("{message}")
-                                }
-                            }
-                            Debug => {
-                                if show_debug {
-                                    debug!("{message}")
-                                }
-                            }
-                        }
-                    }
-                }
-                _ => (),
-            }
-        }
-    }
-}
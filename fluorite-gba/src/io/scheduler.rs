@@ -1,4 +1,7 @@
-use crate::consts::CLOCK_FREQ;
+use crate::{
+    consts::CLOCK_FREQ,
+    state::{SaveState, StateReader, StateWriter},
+};
 use priority_queue::PriorityQueue;
 use std::cmp::Reverse;
 
@@ -18,15 +21,37 @@ impl Scheduler {
     }
 
     pub fn get_next_event(&mut self) -> Option<EventType> {
-        // There should always be at least one event
-        let (_event_type, cycle) = self.event_queue.peek().unwrap();
-        if Reverse(self.cycle) == *cycle {
+        if self.next_event_cycle()? == self.cycle {
             Some(self.event_queue.pop().unwrap().0)
         } else {
             None
         }
     }
 
+    /// The frame sequencer is always scheduled, but a save state may leave the queue empty
+    pub fn next_event_cycle(&self) -> Option<usize> {
+        self.event_queue.peek().map(|(_, cycle)| cycle.0)
+    }
+
+    /// Cycle of the next event that keeps running in stop mode
+    pub fn next_unclocked_event_cycle(&self) -> Option<usize> {
+        self.event_queue
+            .iter()
+            .filter(|(event_type, _)| !event_type.is_clocked())
+            .map(|(_, cycle)| cycle.0)
+            .min()
+    }
+
+    /// Delays the clocked events, so they keep the time left to them while the clock
+    /// is stopped
+    pub fn postpone_clocked(&mut self, clocks: usize) {
+        for (event_type, cycle) in self.event_queue.iter_mut() {
+            if event_type.is_clocked() {
+                cycle.0 += clocks;
+            }
+        }
+    }
+
     pub fn add(&mut self, event: Event) {
         self.event_queue
             .push(event.event_type, Reverse(event.cycle));
@@ -37,6 +62,34 @@ impl Scheduler {
     }
 }
 
+impl SaveState for Scheduler {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.cycle.save_state(w);
+        self.event_queue.len().save_state(w);
+        for (event_type, Reverse(cycle)) in self.event_queue.iter() {
+            event_type.save_state(w);
+            cycle.save_state(w);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        self.cycle.load_state(r);
+        let mut len = 0usize;
+        len.load_state(r);
+        self.event_queue.clear();
+        if !r.check_len(len) {
+            return;
+        }
+        for _ in 0..len {
+            let mut event_type = EventType::FrameSequencer(0);
+            let mut cycle = 0usize;
+            event_type.load_state(r);
+            cycle.load_state(r);
+            self.event_queue.push(event_type, Reverse(cycle));
+        }
+    }
+}
+
 pub struct Event {
     pub cycle: usize,
     pub event_type: EventType,
@@ -46,4 +99,45 @@ pub struct Event {
 pub enum EventType {
     TimerOverflow(usize),
     FrameSequencer(usize),
+    SerialTransfer,
+    SerialPoll,
+}
+
+impl EventType {
+    /// Timers and the sound frame sequencer run off the system clock, which is turned off
+    /// in stop mode
+    pub fn is_clocked(&self) -> bool {
+        matches!(
+            self,
+            EventType::TimerOverflow(_) | EventType::FrameSequencer(_)
+        )
+    }
+}
+
+impl SaveState for EventType {
+    fn save_state(&self, w: &mut StateWriter) {
+        let (tag, index) = match *self {
+            EventType::TimerOverflow(index) => (0u8, index),
+            EventType::FrameSequencer(step) => (1u8, step),
+            EventType::SerialTransfer => (2u8, 0),
+            EventType::SerialPoll => (3u8, 0),
+        };
+        tag.save_state(w);
+        index.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        let mut tag = 0u8;
+        let mut index = 0usize;
+        tag.load_state(r);
+        index.load_state(r);
+        // The index is used to look up the timer or sequencer step when the event runs
+        *self = match tag {
+            0 if index < 4 => EventType::TimerOverflow(index),
+            1 if index < 8 => EventType::FrameSequencer(index),
+            2 => EventType::SerialTransfer,
+            3 => EventType::SerialPoll,
+            _ => return r.invalidate(),
+        };
+    }
 }
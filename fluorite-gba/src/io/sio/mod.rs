@@ -0,0 +1,394 @@
+use self::registers::{RCnt, SioCnt};
+use super::{
+    interrupt_controller::InterruptRequest,
+    scheduler::{Event, EventType, Scheduler},
+};
+use crate::{
+    consts::CLOCK_FREQ,
+    state::{impl_save_state, impl_save_state_enum},
+};
+use std::cell::Cell;
+
+mod link;
+mod registers;
+mod socket;
+
+pub use link::{LinkBackend, Loopback, NoCable, Transfer};
+pub use socket::{LinkAddr, SocketLink};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SioMode {
+    Normal8,
+    Normal32,
+    Multiplayer,
+    Uart,
+    GeneralPurpose,
+    JoyBus,
+}
+
+impl_save_state_enum!(SioMode {
+    Normal8,
+    Normal32,
+    Multiplayer,
+    Uart,
+    GeneralPurpose,
+    JoyBus
+});
+
+pub struct Sio {
+    cnt: SioCnt,
+    rcnt: RCnt,
+    // SIODATA32 or SIOMULTI0-3
+    data: [u16; 4],
+    // SIODATA8 or SIOMLT_SEND
+    send: u16,
+    multi_id: u8,
+    uart_recv: u8,
+    uart_recv_full: Cell<bool>,
+    // JOY Bus, nothing is ever connected so these only hold what the game writes
+    joycnt: u16,
+    joy_recv: u32,
+    joy_trans: u32,
+    joystat: u16,
+
+    // Transfer in progress. SerialTransfer events start requested transfers and finish running ones
+    transfer: Option<Transfer>,
+    link: Box<dyn LinkBackend>,
+}
+
+impl_save_state!(Sio {
+    cnt,
+    rcnt,
+    data,
+    send,
+    multi_id,
+    uart_recv,
+    uart_recv_full,
+    joycnt,
+    joy_recv,
+    joy_trans,
+    joystat,
+    transfer,
+});
+
+impl Sio {
+    const BAUD_RATES: [usize; 4] = [9600, 38400, 57600, 115200];
+
+    pub fn new() -> Sio {
+        Sio {
+            cnt: SioCnt::new(),
+            rcnt: RCnt::new(),
+            data: [0; 4],
+            send: 0,
+            multi_id: 0,
+            uart_recv: 0,
+            uart_recv_full: Cell::new(false),
+            joycnt: 0,
+            joy_recv: 0,
+            joy_trans: 0,
+            joystat: 0,
+
+            transfer: None,
+            link: Box::new(NoCable),
+        }
+    }
+
+    /// Resets the registers, the cable stays plugged in
+    pub fn reset(&mut self, scheduler: &mut Scheduler) {
+        let link = std::mem::replace(&mut self.link, Box::new(NoCable));
+        *self = Sio::new();
+        self.set_link(scheduler, link);
+    }
+
+    pub fn set_link(&mut self, scheduler: &mut Scheduler, link: Box<dyn LinkBackend>) {
+        self.link = link;
+        self.schedule_poll(scheduler);
+        self.publish();
+    }
+
+    /// Only polls the cable when another unit can start transfers
+    pub fn schedule_poll(&self, scheduler: &mut Scheduler) {
+        scheduler.remove(EventType::SerialPoll);
+        if self.link.connected() > 1 {
+            scheduler.add(Event {
+                cycle: scheduler.cycle + self.link.poll_interval(),
+                event_type: EventType::SerialPoll,
+            });
+        }
+    }
+
+    pub fn mode(&self) -> SioMode {
+        match (self.rcnt.mode(), self.cnt.mode()) {
+            (2, _) => SioMode::GeneralPurpose,
+            (3, _) => SioMode::JoyBus,
+            (_, 0) => SioMode::Normal8,
+            (_, 1) => SioMode::Normal32,
+            (_, 2) => SioMode::Multiplayer,
+            (_, 3) => SioMode::Uart,
+            _ => unreachable!(),
+        }
+    }
+
+    fn outgoing(&self) -> u32 {
+        match self.mode() {
+            SioMode::Normal8 | SioMode::Uart => self.send as u32 & 0xFF,
+            SioMode::Normal32 => self.data[0] as u32 | (self.data[1] as u32) << 16,
+            SioMode::Multiplayer => self.send as u32,
+            SioMode::GeneralPurpose | SioMode::JoyBus => !0,
+        }
+    }
+
+    // Lets the other units see our data in case they start the next transfer
+    fn publish(&mut self) {
+        let data = self.outgoing();
+        self.link.set_data(data);
+    }
+
+    fn transfer_len(&self) -> usize {
+        let bit_len = CLOCK_FREQ / Self::BAUD_RATES[self.cnt.baud_rate() as usize];
+        match self.mode() {
+            SioMode::Normal8 | SioMode::Normal32 => {
+                let bits = if self.mode() == SioMode::Normal8 {
+                    8
+                } else {
+                    32
+                };
+                // 256KHz or 2MHz shift clock
+                bits * if self.cnt.clock_2mhz() { 8 } else { 64 }
+            }
+            // Every unit sends a start bit, 16 data bits and a stop bit
+            SioMode::Multiplayer => self.link.connected() * 18 * bit_len,
+            SioMode::Uart => 10 * bit_len,
+            SioMode::GeneralPurpose | SioMode::JoyBus => unreachable!(),
+        }
+    }
+
+    // Games often write the start bit together with the mode, which is in the upper byte,
+    // so the transfer only starts on the next cycle
+    fn request_transfer(&mut self, scheduler: &mut Scheduler) {
+        self.transfer = None;
+        scheduler.remove(EventType::SerialTransfer);
+        scheduler.add(Event {
+            cycle: scheduler.cycle + 1,
+            event_type: EventType::SerialTransfer,
+        });
+    }
+
+    fn start_transfer(&mut self, scheduler: &mut Scheduler) {
+        let can_start = match self.mode() {
+            SioMode::Normal8 | SioMode::Normal32 => self.cnt.start() && self.cnt.internal_clock(),
+            SioMode::Multiplayer => self.cnt.start() && self.link.id() == 0,
+            SioMode::Uart => self.cnt.send_enable(),
+            SioMode::GeneralPurpose | SioMode::JoyBus => false,
+        };
+        if !can_start {
+            // Children only see the start bit as a busy flag
+            if self.mode() == SioMode::Multiplayer {
+                self.cnt.set_start(false);
+            }
+            return;
+        }
+
+        self.transfer = Some(self.link.start(self.mode(), self.outgoing()));
+        scheduler.add(Event {
+            cycle: scheduler.cycle + self.transfer_len(),
+            event_type: EventType::SerialTransfer,
+        });
+    }
+
+    /// Starts a requested transfer or finishes the running one and returns the requested interrupts
+    pub fn clock_transfer(&mut self, scheduler: &mut Scheduler) -> InterruptRequest {
+        if self.transfer.is_some() {
+            self.finish_transfer()
+        } else {
+            self.start_transfer(scheduler);
+            InterruptRequest::new()
+        }
+    }
+
+    fn stop_transfer(&mut self, scheduler: &mut Scheduler) {
+        self.transfer = None;
+        scheduler.remove(EventType::SerialTransfer);
+    }
+
+    fn interrupt(&self) -> InterruptRequest {
+        InterruptRequest::new().with_serial(self.cnt.irq())
+    }
+
+    // Stores the received data once the transfer is done
+    fn finish_transfer(&mut self) -> InterruptRequest {
+        let transfer = match self.transfer.take() {
+            Some(transfer) => transfer,
+            None => return InterruptRequest::new(),
+        };
+        // In normal mode the master receives from the slave and the slave from the master
+        let received = transfer.data[self.cnt.internal_clock() as usize];
+        match transfer.mode {
+            SioMode::Normal8 => self.send = self.send & 0xFF00 | received as u16 & 0xFF,
+            SioMode::Normal32 => {
+                self.data[0] = received as u16;
+                self.data[1] = (received >> 16) as u16;
+            }
+            SioMode::Multiplayer => {
+                for (data, received) in self.data.iter_mut().zip(transfer.data) {
+                    *data = received as u16;
+                }
+                self.multi_id = self.link.id() as u8;
+            }
+            SioMode::Uart => return self.interrupt(),
+            SioMode::GeneralPurpose | SioMode::JoyBus => unreachable!(),
+        }
+        self.cnt.set_start(false);
+        self.publish();
+        self.interrupt()
+    }
+
+    /// Receives transfers started by other units and returns the requested interrupts
+    pub fn poll(&mut self, scheduler: &mut Scheduler) -> InterruptRequest {
+        self.schedule_poll(scheduler);
+        let transfer = match self.link.poll() {
+            Some(transfer) if transfer.mode == self.mode() => transfer,
+            _ => return InterruptRequest::new(),
+        };
+        match transfer.mode {
+            SioMode::Normal8 | SioMode::Normal32
+                if self.cnt.start() && !self.cnt.internal_clock() =>
+            {
+                self.stop_transfer(scheduler);
+                self.transfer = Some(transfer);
+                self.finish_transfer()
+            }
+            SioMode::Multiplayer if self.link.id() != 0 => {
+                self.stop_transfer(scheduler);
+                self.transfer = Some(transfer);
+                self.finish_transfer()
+            }
+            SioMode::Uart if self.cnt.recv_enable() => {
+                self.uart_recv = transfer.data[0] as u8;
+                self.uart_recv_full.set(true);
+                self.interrupt()
+            }
+            _ => InterruptRequest::new(),
+        }
+    }
+
+    fn read_cnt(&self) -> SioCnt {
+        let mut cnt = self.cnt;
+        match self.mode() {
+            SioMode::Multiplayer => {
+                cnt.set_si(self.link.id() != 0);
+                cnt.set_sd(self.link.connected() > 1);
+                cnt.set_multi_id(self.multi_id);
+                cnt.set_error(false);
+            }
+            SioMode::Uart => {
+                cnt.set_send_full(self.transfer.is_some());
+                cnt.set_recv_empty(!self.uart_recv_full.get());
+                cnt.set_error(false);
+            }
+            _ => (),
+        }
+        cnt
+    }
+
+    fn read_rcnt(&self) -> RCnt {
+        let mut rcnt = self.rcnt;
+        if self.mode() == SioMode::GeneralPurpose {
+            // Nothing drives the input pins so they're pulled high
+            let inputs = !self.rcnt.direction() & 0xF;
+            rcnt.set_data(self.rcnt.data() | inputs);
+        }
+        rcnt
+    }
+
+    pub fn read(&self, addr: u32) -> u8 {
+        if addr == 0x0400012A && self.mode() == SioMode::Uart {
+            self.uart_recv_full.set(false);
+        }
+        self.peek(addr)
+    }
+
+    /// Reads a register without emptying the UART receive buffer
+    pub fn peek(&self, addr: u32) -> u8 {
+        match addr {
+            0x04000120..=0x04000127 => {
+                (self.data[(addr as usize - 0x04000120) / 2] >> (8 * (addr & 1))) as u8
+            }
+            0x04000128 => self.read_cnt().read::<0>(),
+            0x04000129 => self.read_cnt().read::<1>(),
+            0x0400012A if self.mode() == SioMode::Uart => self.uart_recv,
+            0x0400012A => self.send as u8,
+            0x0400012B => (self.send >> 8) as u8,
+            0x04000134 => self.read_rcnt().read::<0>(),
+            0x04000135 => self.read_rcnt().read::<1>(),
+            0x04000140 => self.joycnt as u8,
+            0x04000141 => (self.joycnt >> 8) as u8,
+            0x04000150..=0x04000153 => (self.joy_recv >> (8 * (addr & 3))) as u8,
+            0x04000154..=0x04000157 => (self.joy_trans >> (8 * (addr & 3))) as u8,
+            0x04000158 => self.joystat as u8,
+            0x04000159 => (self.joystat >> 8) as u8,
+            _ => 0,
+        }
+    }
+
+    pub fn write(&mut self, scheduler: &mut Scheduler, addr: u32, value: u8) {
+        match addr {
+            0x04000120..=0x04000127 => {
+                let data = &mut self.data[(addr as usize - 0x04000120) / 2];
+                let shift = 8 * (addr & 1);
+                *data = *data & !(0xFF << shift) | (value as u16) << shift;
+                self.publish();
+            }
+            0x04000128 => self.write_cnt::<0>(scheduler, value),
+            0x04000129 => self.write_cnt::<1>(scheduler, value),
+            0x0400012A => {
+                self.send = self.send & !0x00FF | value as u16;
+                self.publish();
+                if self.mode() == SioMode::Uart && self.cnt.send_enable() {
+                    self.request_transfer(scheduler);
+                }
+            }
+            0x0400012B => {
+                self.send = self.send & !0xFF00 | (value as u16) << 8;
+                self.publish();
+            }
+            0x04000134 => self.rcnt.write::<0>(value),
+            0x04000135 => {
+                self.rcnt.write::<1>(value);
+                self.publish();
+            }
+            // Status flags are acknowledged by writing 1
+            0x04000140 => {
+                self.joycnt = self.joycnt & !(value as u16 & 0x7) & !0x40 | value as u16 & 0x40
+            }
+            0x04000150..=0x04000153 => {
+                let shift = 8 * (addr & 3);
+                self.joy_recv = self.joy_recv & !(0xFF << shift) | (value as u32) << shift;
+            }
+            0x04000154..=0x04000157 => {
+                let shift = 8 * (addr & 3);
+                self.joy_trans = self.joy_trans & !(0xFF << shift) | (value as u32) << shift;
+                self.joystat |= 0x8;
+            }
+            0x04000158 => self.joystat = self.joystat & !0x30 | value as u16 & 0x30,
+            _ => (),
+        }
+    }
+
+    fn write_cnt<const BYTE: u8>(&mut self, scheduler: &mut Scheduler, value: u8) {
+        let was_started = self.cnt.start();
+        // Status bits are filled in when reading
+        self.cnt.write::<BYTE>(value, 0x7F8F);
+
+        // Bit 7 selects the data length in UART mode
+        if self.mode() != SioMode::Uart {
+            if !was_started && self.cnt.start() {
+                // With an external clock the transfer starts when the master sends
+                self.request_transfer(scheduler);
+            } else if was_started && !self.cnt.start() {
+                self.stop_transfer(scheduler);
+            }
+        }
+        self.publish();
+    }
+}
@@ -0,0 +1,87 @@
+use crate::state::impl_save_state;
+use fluorite_common::bitfield;
+
+bitfield! {
+    /// SIOCNT, the meaning of most bits depends on the selected mode
+    #[derive(Clone, Copy)]
+    pub struct SioCnt(u16) {
+        pub raw: u16 [read_only] @ ..,
+        // Normal
+        pub internal_clock: bool @ 0,
+        pub clock_2mhz: bool @ 1,
+        // Multiplayer and UART
+        pub baud_rate: u8 @ 0..=1,
+        pub si: bool @ 2,
+        pub sd: bool @ 3,
+        pub multi_id: u8 @ 4..=5,
+        pub send_full: bool @ 4,
+        pub recv_empty: bool @ 5,
+        pub error: bool @ 6,
+        pub start: bool @ 7,
+        pub send_enable: bool @ 10,
+        pub recv_enable: bool @ 11,
+        pub mode: u8 @ 12..=13,
+        pub irq: bool @ 14,
+    }
+}
+
+impl SioCnt {
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    pub fn read<const BYTE: u8>(&self) -> u8 {
+        match BYTE {
+            0 => self.0 as u8,
+            1 => (self.0 >> 8) as u8,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Only writes the bits in `mask`, the rest are status bits
+    pub fn write<const BYTE: u8>(&mut self, value: u8, mask: u16) {
+        let (value, mask) = match BYTE {
+            0 => (value as u16, mask & 0x00FF),
+            1 => ((value as u16) << 8, mask & 0xFF00),
+            _ => unreachable!(),
+        };
+        self.0 = self.0 & !mask | value & mask;
+    }
+}
+
+bitfield! {
+    /// RCNT, selects general purpose and JOY Bus mode and drives the pins in general purpose mode
+    #[derive(Clone, Copy)]
+    pub struct RCnt(u16) {
+        pub raw: u16 [read_only] @ ..,
+        pub data: u8 @ 0..=3,
+        pub direction: u8 @ 4..=7,
+        pub irq: bool @ 8,
+        pub mode: u8 @ 14..=15,
+    }
+}
+
+impl RCnt {
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    pub fn read<const BYTE: u8>(&self) -> u8 {
+        match BYTE {
+            0 => self.0 as u8,
+            1 => (self.0 >> 8) as u8,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn write<const BYTE: u8>(&mut self, value: u8) {
+        self.0 = match BYTE {
+            0 => self.0 & !0x00FF | value as u16,
+            1 => self.0 & !0xFF00 | (value as u16) << 8 & 0xC1FF,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl_save_state!(SioCnt { 0 });
+impl_save_state!(RCnt { 0 });
@@ -0,0 +1,412 @@
+use super::{LinkBackend, SioMode, Transfer};
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::{TcpListener, TcpStream},
+    str::FromStr,
+};
+#[cfg(unix)]
+use std::{
+    fs,
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+};
+
+/// Where the host of a socket link listens
+#[derive(Clone, Debug)]
+pub enum LinkAddr {
+    /// Anything `ToSocketAddrs` understands, like `127.0.0.1:5477`
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for LinkAddr {
+    type Err = Infallible;
+
+    /// Parses `unix:<path>` as a Unix socket and anything else as a TCP address
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        #[cfg(unix)]
+        if let Some(path) = s.strip_prefix("unix:") {
+            return Ok(LinkAddr::Unix(path.into()));
+        }
+        Ok(LinkAddr::Tcp(s.to_string()))
+    }
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn connect(addr: &LinkAddr) -> io::Result<Stream> {
+        match addr {
+            LinkAddr::Tcp(addr) => {
+                let stream = TcpStream::connect(addr)?;
+                stream.set_nodelay(true)?;
+                Ok(Stream::Tcp(stream))
+            }
+            #[cfg(unix)]
+            LinkAddr::Unix(path) => Ok(Stream::Unix(UnixStream::connect(path)?)),
+        }
+    }
+
+    fn try_clone(&self) -> io::Result<Stream> {
+        match self {
+            Stream::Tcp(stream) => Ok(Stream::Tcp(stream.try_clone()?)),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Ok(Stream::Unix(stream.try_clone()?)),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl Listener {
+    fn bind(addr: &LinkAddr) -> io::Result<Listener> {
+        match addr {
+            LinkAddr::Tcp(addr) => Ok(Listener::Tcp(TcpListener::bind(addr)?)),
+            #[cfg(unix)]
+            LinkAddr::Unix(path) => {
+                // A previous host may have left its socket behind
+                if fs::metadata(path).is_ok() {
+                    fs::remove_file(path)?;
+                }
+                Ok(Listener::Unix(UnixListener::bind(path)?))
+            }
+        }
+    }
+
+    fn accept(&self) -> io::Result<Stream> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nodelay(true)?;
+                Ok(Stream::Tcp(stream))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => Ok(Stream::Unix(listener.accept()?.0)),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Message {
+    Hello { id: u8, players: u8 },
+    Data { id: u8, data: u32 },
+    Transfer(Transfer),
+    Sync,
+}
+
+impl Message {
+    const LEN: usize = 18;
+
+    fn encode(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        match *self {
+            Message::Hello { id, players } => bytes[..3].copy_from_slice(&[0, id, players]),
+            Message::Data { id, data } => {
+                bytes[..2].copy_from_slice(&[1, id]);
+                bytes[2..6].copy_from_slice(&data.to_le_bytes());
+            }
+            Message::Transfer(transfer) => {
+                bytes[..2].copy_from_slice(&[2, transfer.mode as u8]);
+                for (i, data) in transfer.data.iter().enumerate() {
+                    bytes[2 + 4 * i..6 + 4 * i].copy_from_slice(&data.to_le_bytes());
+                }
+            }
+            Message::Sync => bytes[0] = 3,
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8; Self::LEN]) -> io::Result<Message> {
+        let word = |i: usize| u32::from_le_bytes(bytes[2 + 4 * i..6 + 4 * i].try_into().unwrap());
+        let message = match bytes[0] {
+            0 => Message::Hello {
+                id: bytes[1],
+                players: bytes[2],
+            },
+            1 if (bytes[1] as usize) < 4 => Message::Data {
+                id: bytes[1],
+                data: word(0),
+            },
+            2 => {
+                let mode = match bytes[1] {
+                    0 => SioMode::Normal8,
+                    1 => SioMode::Normal32,
+                    2 => SioMode::Multiplayer,
+                    3 => SioMode::Uart,
+                    _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "bad link mode")),
+                };
+                Message::Transfer(Transfer {
+                    mode,
+                    data: [word(0), word(1), word(2), word(3)],
+                })
+            }
+            3 => Message::Sync,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "bad link message",
+                ))
+            }
+        };
+        Ok(message)
+    }
+}
+
+struct Peer {
+    reader: BufReader<Stream>,
+    writer: BufWriter<Stream>,
+}
+
+impl Peer {
+    fn new(stream: Stream) -> io::Result<Peer> {
+        Ok(Peer {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+        })
+    }
+
+    fn send(&mut self, message: Message) -> io::Result<()> {
+        self.writer.write_all(&message.encode())
+    }
+
+    fn receive(&mut self) -> io::Result<Message> {
+        let mut bytes = [0; Message::LEN];
+        self.reader.read_exact(&mut bytes)?;
+        Message::decode(&bytes)
+    }
+}
+
+/// Connects up to four emulators, usually in different processes, over a socket.
+///
+/// The host relays everything between the clients. All units meet at a barrier every time
+/// their serial port polls the cable, so none of them can run more than one poll interval
+/// ahead of the others.
+pub struct SocketLink {
+    id: usize,
+    players: usize,
+    // The host talks to every client, clients only talk to the host
+    peers: Vec<Peer>,
+    data: [u32; 4],
+    received: VecDeque<Transfer>,
+}
+
+impl SocketLink {
+    /// Waits until `players - 1` clients have joined on `addr`. The host is player 0.
+    pub fn host(addr: &LinkAddr, players: usize) -> io::Result<SocketLink> {
+        if !(2..=4).contains(&players) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a link cable connects 2 to 4 players",
+            ));
+        }
+
+        let listener = Listener::bind(addr)?;
+        let mut peers = Vec::new();
+        for _ in 1..players {
+            peers.push(Peer::new(listener.accept()?)?);
+        }
+        // Clients are only told their number once everyone is connected
+        for (i, peer) in peers.iter_mut().enumerate() {
+            peer.send(Message::Hello {
+                id: i as u8 + 1,
+                players: players as u8,
+            })?;
+            peer.writer.flush()?;
+        }
+
+        Ok(SocketLink::new(0, players, peers))
+    }
+
+    /// Connects to a host and waits until every player has joined
+    pub fn join(addr: &LinkAddr) -> io::Result<SocketLink> {
+        let mut host = Peer::new(Stream::connect(addr)?)?;
+        match host.receive()? {
+            Message::Hello { id, players } if id < players && players <= 4 => {
+                Ok(SocketLink::new(id as usize, players as usize, vec![host]))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "expected a hello from the link host",
+            )),
+        }
+    }
+
+    fn new(id: usize, players: usize, peers: Vec<Peer>) -> SocketLink {
+        SocketLink {
+            id,
+            players,
+            peers,
+            data: [!0; 4],
+            received: VecDeque::new(),
+        }
+    }
+
+    fn is_host(&self) -> bool {
+        self.id == 0
+    }
+
+    fn is_connected(&self) -> bool {
+        !self.peers.is_empty()
+    }
+
+    // Sends a message to every other unit, relayed by the host
+    fn broadcast(&mut self, message: Message, except: Option<usize>) {
+        let result = self
+            .peers
+            .iter_mut()
+            .enumerate()
+            .filter(|(i, _)| Some(*i) != except)
+            .try_for_each(|(_, peer)| peer.send(message));
+        self.check(result);
+    }
+
+    fn check(&mut self, result: io::Result<()>) {
+        if let Err(e) = result {
+            warn!("Link cable disconnected: {e}");
+            self.peers.clear();
+        }
+    }
+
+    fn handle(&mut self, message: Message) -> io::Result<()> {
+        match message {
+            Message::Data { id, data } => self.data[id as usize] = data,
+            Message::Transfer(transfer) => self.received.push_back(transfer),
+            Message::Hello { .. } | Message::Sync => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unexpected link message",
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        if self.is_host() {
+            // Wait for every client, passing on what they sent since the last sync
+            for i in 0..self.peers.len() {
+                loop {
+                    let message = self.peers[i].receive()?;
+                    if let Message::Sync = message {
+                        break;
+                    }
+                    self.handle(message)?;
+                    for (_, peer) in self.peers.iter_mut().enumerate().filter(|(j, _)| *j != i) {
+                        peer.send(message)?;
+                    }
+                }
+            }
+            for peer in &mut self.peers {
+                peer.send(Message::Sync)?;
+                peer.writer.flush()?;
+            }
+        } else {
+            let host = &mut self.peers[0];
+            host.send(Message::Sync)?;
+            host.writer.flush()?;
+            loop {
+                match self.peers[0].receive()? {
+                    Message::Sync => break,
+                    message => self.handle(message)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl LinkBackend for SocketLink {
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn connected(&self) -> usize {
+        if self.is_connected() {
+            self.players
+        } else {
+            1
+        }
+    }
+
+    fn set_data(&mut self, data: u32) {
+        if self.data[self.id] != data {
+            self.data[self.id] = data;
+            self.broadcast(
+                Message::Data {
+                    id: self.id as u8,
+                    data,
+                },
+                None,
+            );
+        }
+    }
+
+    fn start(&mut self, mode: SioMode, data: u32) -> Transfer {
+        self.data[self.id] = data;
+        let mut transfer = Transfer {
+            mode,
+            ..Default::default()
+        };
+        transfer.data[0] = data;
+        if self.is_connected() {
+            let others = (0..self.players).filter(|&id| id != self.id);
+            for (slot, id) in transfer.data[1..].iter_mut().zip(others) {
+                *slot = self.data[id];
+            }
+            self.broadcast(Message::Transfer(transfer), None);
+        }
+        transfer
+    }
+
+    // Every poll is a round trip through the host, a multiplayer transfer still takes longer
+    fn poll_interval(&self) -> usize {
+        4096
+    }
+
+    fn poll(&mut self) -> Option<Transfer> {
+        if self.is_connected() {
+            let result = self.sync();
+            self.check(result);
+        }
+        self.received.pop_front()
+    }
+}
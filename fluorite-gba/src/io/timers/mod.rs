@@ -1,4 +1,5 @@
 use self::registers::TmCnt;
+use crate::state::impl_save_state;
 use super::{
     interrupt_controller::InterruptRequest,
     scheduler::{Event, EventType, Scheduler},
@@ -10,9 +11,18 @@ pub struct Timers {
     pub timers: [Timer; 4],
 }
 
+impl_save_state!(Timers { timers });
+
 impl Timers {
     pub const PRESCALERS: [usize; 4] = [1, 64, 256, 1024];
 
+    /// Keeps the counters where they are while the clock is stopped for `clocks` cycles
+    pub fn postpone(&mut self, clocks: usize) {
+        for timer in self.timers.iter_mut() {
+            timer.start_cycle += clocks;
+        }
+    }
+
     pub fn new() -> Timers {
         Timers {
             timers: [
@@ -40,6 +50,17 @@ pub struct Timer {
     timer_len: usize,
 }
 
+impl_save_state!(Timer {
+    reload,
+    cnt,
+    index,
+    interrupt,
+    counter,
+    start_cycle,
+    time_till_first_clock,
+    timer_len,
+});
+
 impl Timer {
     pub fn new(index: usize, interrupt: InterruptRequest) -> Timer {
         Timer {
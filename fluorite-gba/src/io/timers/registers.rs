@@ -1,3 +1,5 @@
+use crate::state::impl_save_state;
+
 #[derive(Clone, Copy)]
 pub struct TmCnt {
     pub prescaler: u8,
@@ -6,6 +8,13 @@ pub struct TmCnt {
     pub start: bool,
 }
 
+impl_save_state!(TmCnt {
+    prescaler,
+    count_up,
+    irq,
+    start
+});
+
 impl TmCnt {
     pub fn new() -> TmCnt {
         TmCnt {
@@ -10,11 +10,13 @@ extern crate log;
 extern crate num_traits as num;
 
 pub mod arm;
+pub mod bios;
 pub mod consts;
+pub mod debugger;
 pub mod gba;
+pub mod gdb;
 pub mod io;
-
-pub(crate) static BIOS: &[u8] = include_bytes!("../../roms/gba_bios.bin");
+pub mod state;
 
 pub trait AudioInterface {
     fn write(&mut self, samples: [i16; 2]);
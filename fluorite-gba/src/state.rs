@@ -0,0 +1,358 @@
+use std::{cell::Cell, collections::VecDeque, fmt};
+
+const MAGIC: [u8; 4] = *b"FLST";
+const VERSION: u32 = 8;
+const HEADER_SIZE: usize = 4 + 4 + 8 + 4 + 8 + 8;
+
+/// A component whose state can be written to and restored from a save state.
+pub trait SaveState {
+    fn save_state(&self, w: &mut StateWriter);
+    fn load_state(&mut self, r: &mut StateReader);
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StateError {
+    InvalidHeader,
+    UnsupportedVersion(u32),
+    RomMismatch,
+    BiosMismatch,
+    Corrupt,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::InvalidHeader => write!(f, "not a fluorite save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "unsupported save state version {version} (expected {VERSION})"
+                )
+            }
+            StateError::RomMismatch => write!(f, "save state was created with a different rom"),
+            StateError::BiosMismatch => {
+                write!(f, "save state was created with a different kind of bios")
+            }
+            StateError::Corrupt => write!(f, "save state is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+#[derive(Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self { data: Vec::new() }
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// Prepends the header to the written payload. `hle_bios` tells whether the state
+    /// was created with the built-in BIOS instead of a real one.
+    pub fn finish(self, rom_hash: u64, hle_bios: bool) -> Vec<u8> {
+        let mut state = Vec::with_capacity(HEADER_SIZE + self.data.len());
+        state.extend_from_slice(&MAGIC);
+        state.extend_from_slice(&VERSION.to_le_bytes());
+        state.extend_from_slice(&rom_hash.to_le_bytes());
+        state.extend_from_slice(&(hle_bios as u32).to_le_bytes());
+        state.extend_from_slice(&(self.data.len() as u64).to_le_bytes());
+        state.extend_from_slice(&hash(&self.data).to_le_bytes());
+        state.extend_from_slice(&self.data);
+        state
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    valid: bool,
+}
+
+impl<'a> StateReader<'a> {
+    /// Validates the header of `state` and returns a reader over its payload
+    pub fn new(state: &'a [u8], rom_hash: u64, hle_bios: bool) -> Result<Self, StateError> {
+        if state.len() < HEADER_SIZE || state[0..4] != MAGIC {
+            return Err(StateError::InvalidHeader);
+        }
+
+        let field = |offset: usize, len: usize| {
+            let mut bytes = [0; 8];
+            bytes[..len].copy_from_slice(&state[offset..offset + len]);
+            u64::from_le_bytes(bytes)
+        };
+
+        let version = field(4, 4) as u32;
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        if field(8, 8) != rom_hash {
+            return Err(StateError::RomMismatch);
+        }
+        // The bios is mapped into memory, its state doesn't carry over to the other kind
+        if field(16, 4) != hle_bios as u64 {
+            return Err(StateError::BiosMismatch);
+        }
+
+        let payload = &state[HEADER_SIZE..];
+        if field(20, 8) != payload.len() as u64 || field(28, 8) != hash(payload) {
+            return Err(StateError::Corrupt);
+        }
+
+        Ok(Self {
+            data: payload,
+            pos: 0,
+            valid: true,
+        })
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> &'a [u8] {
+        if self.pos + len > self.data.len() {
+            self.valid = false;
+            self.pos = self.data.len();
+            return &[];
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        bytes
+    }
+
+    /// Checks a decoded element count against the remaining payload, where every element
+    /// takes at least a byte. A count that can't fit marks the state as malformed, so
+    /// it isn't used to allocate.
+    pub fn check_len(&mut self, len: usize) -> bool {
+        if len > self.data.len() - self.pos {
+            self.invalidate();
+            return false;
+        }
+        true
+    }
+
+    /// Marks the state as malformed. Used when a decoded value is out of range.
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    /// Consumes the reader, checking that the whole payload was used
+    pub fn finish(self) -> Result<(), StateError> {
+        if self.valid && self.pos == self.data.len() {
+            Ok(())
+        } else {
+            Err(StateError::Corrupt)
+        }
+    }
+}
+
+/// 64 bit FNV-1a, used to identify roms and to detect corrupted states
+pub fn hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+macro_rules! impl_primitive {
+    ($($t:ty),*) => {
+        $(
+            impl SaveState for $t {
+                fn save_state(&self, w: &mut StateWriter) {
+                    w.write_bytes(&self.to_le_bytes());
+                }
+
+                fn load_state(&mut self, r: &mut StateReader) {
+                    let bytes = r.read_bytes(std::mem::size_of::<$t>());
+                    if let Ok(bytes) = bytes.try_into() {
+                        *self = <$t>::from_le_bytes(bytes);
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_primitive!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl SaveState for usize {
+    fn save_state(&self, w: &mut StateWriter) {
+        (*self as u64).save_state(w)
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        let mut value = 0u64;
+        value.load_state(r);
+        *self = value as usize;
+    }
+}
+
+impl SaveState for bool {
+    fn save_state(&self, w: &mut StateWriter) {
+        (*self as u8).save_state(w)
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        let mut value = 0u8;
+        value.load_state(r);
+        *self = value != 0;
+    }
+}
+
+impl SaveState for char {
+    fn save_state(&self, w: &mut StateWriter) {
+        (*self as u32).save_state(w)
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        let mut value = 0u32;
+        value.load_state(r);
+        match char::from_u32(value) {
+            Some(c) => *self = c,
+            None => r.invalidate(),
+        }
+    }
+}
+
+impl<T: SaveState, const N: usize> SaveState for [T; N] {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.iter().for_each(|x| x.save_state(w));
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        self.iter_mut().for_each(|x| x.load_state(r));
+    }
+}
+
+impl SaveState for Box<[u8]> {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.len().save_state(w);
+        w.write_bytes(self);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        let mut len = 0usize;
+        len.load_state(r);
+        if len != self.len() {
+            r.invalidate();
+            return;
+        }
+        let bytes = r.read_bytes(len);
+        if bytes.len() == len {
+            self.copy_from_slice(bytes);
+        }
+    }
+}
+
+impl<T: SaveState + Default> SaveState for Vec<T> {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.len().save_state(w);
+        self.iter().for_each(|x| x.save_state(w));
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        let mut len = 0usize;
+        len.load_state(r);
+        self.clear();
+        if !r.check_len(len) {
+            return;
+        }
+        for _ in 0..len {
+            let mut value = T::default();
+            value.load_state(r);
+            self.push(value);
+        }
+    }
+}
+
+impl<T: SaveState + Default> SaveState for VecDeque<T> {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.len().save_state(w);
+        self.iter().for_each(|x| x.save_state(w));
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        let mut len = 0usize;
+        len.load_state(r);
+        self.clear();
+        if !r.check_len(len) {
+            return;
+        }
+        for _ in 0..len {
+            let mut value = T::default();
+            value.load_state(r);
+            self.push_back(value);
+        }
+    }
+}
+
+impl<T: SaveState + Default> SaveState for Option<T> {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.is_some().save_state(w);
+        if let Some(value) = self {
+            value.save_state(w);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        let mut is_some = false;
+        is_some.load_state(r);
+        *self = if is_some {
+            let mut value = T::default();
+            value.load_state(r);
+            Some(value)
+        } else {
+            None
+        };
+    }
+}
+
+impl<T: SaveState + Copy> SaveState for Cell<T> {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.get().save_state(w)
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) {
+        self.get_mut().load_state(r)
+    }
+}
+
+/// Implements `SaveState` for a struct by saving the listed fields in order
+macro_rules! impl_save_state {
+    ($ty:ty { $($field:tt),* $(,)? }) => {
+        impl $crate::state::SaveState for $ty {
+            fn save_state(&self, w: &mut $crate::state::StateWriter) {
+                $( $crate::state::SaveState::save_state(&self.$field, w); )*
+            }
+
+            fn load_state(&mut self, r: &mut $crate::state::StateReader) {
+                $( $crate::state::SaveState::load_state(&mut self.$field, r); )*
+            }
+        }
+    };
+}
+
+/// Implements `SaveState` for a fieldless enum by saving its discriminant
+macro_rules! impl_save_state_enum {
+    ($ty:ty { $($variant:ident),* $(,)? }) => {
+        impl $crate::state::SaveState for $ty {
+            fn save_state(&self, w: &mut $crate::state::StateWriter) {
+                $crate::state::SaveState::save_state(&(*self as u8), w);
+            }
+
+            fn load_state(&mut self, r: &mut $crate::state::StateReader) {
+                let mut value = 0u8;
+                $crate::state::SaveState::load_state(&mut value, r);
+                *self = match value {
+                    $( x if x == <$ty>::$variant as u8 => <$ty>::$variant, )*
+                    _ => return r.invalidate(),
+                };
+            }
+        }
+    };
+}
+
+pub(crate) use impl_save_state;
+pub(crate) use impl_save_state_enum;
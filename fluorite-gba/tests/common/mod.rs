@@ -0,0 +1,63 @@
+//! Helpers shared by the integration tests
+
+#![allow(dead_code)]
+
+use fluorite_gba::{consts::CLOCKS_PER_FRAME, gba::Gba, state, AudioInterface};
+use std::{
+    path::{Path, PathBuf},
+    sync::Once,
+};
+
+struct NoAudio;
+
+impl AudioInterface for NoAudio {
+    fn write(&mut self, _: [i16; 2]) {}
+}
+
+/// The audio device is global, so it is set up once for all tests of a binary
+pub fn init_audio() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| Gba::load_audio(Box::leak(Box::new(NoAudio))));
+}
+
+pub fn rom(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../roms")
+        .join(name)
+}
+
+/// Boots `name` from `roms/` with the built-in BIOS, without touching its save file
+pub fn boot(name: &str) -> Gba {
+    init_audio();
+    let mut gba = Gba::new(None).unwrap();
+    gba.set_persist(false);
+    gba.load_rom(rom(name));
+    gba.reset();
+    gba
+}
+
+pub fn run_frames(gba: &mut Gba, frames: usize) {
+    for _ in 0..frames {
+        gba.run(CLOCKS_PER_FRAME);
+    }
+}
+
+pub fn frame_hash(gba: &Gba) -> u64 {
+    let bytes: Vec<u8> = gba
+        .get_pixels()
+        .iter()
+        .flat_map(|p| (p & 0x7FFF).to_le_bytes())
+        .collect();
+    state::hash(&bytes)
+}
+
+/// Boots `name` with the BIOS dump in `roms/`
+pub fn boot_with_bios(name: &str) -> Gba {
+    init_audio();
+    let bios = std::fs::read(rom("gba_bios.bin")).unwrap();
+    let mut gba = Gba::new(Some(bios)).unwrap();
+    gba.set_persist(false);
+    gba.load_rom(rom(name));
+    gba.reset();
+    gba
+}
@@ -0,0 +1,195 @@
+//! Stops `Gba::run` with breakpoints, steps and watchpoints in small programs that are
+//! written to IWRAM and run instead of the rom.
+
+mod common;
+
+use common::boot;
+use fluorite_gba::{
+    arm::registers::Reg,
+    consts::CLOCKS_PER_FRAME,
+    debugger::{
+        Breakpoint, Comparison, Condition, Step, StopReason, WatchHit, WatchKind, Watchpoint,
+    },
+    gba::Gba,
+};
+
+const ROM: &str = "tonc/first.gba";
+const CODE: u32 = 0x0300_0000;
+const DATA: u32 = 0x0200_0000;
+
+// Counts r0 up, calls a function that counts r2 up and stores r0 to the address in r1
+const ARM: [u32; 7] = [
+    0xE3A0_0000, // 00: mov r0, #0
+    0xE280_0001, // 04: add r0, r0, #1
+    0xEB00_0001, // 08: bl 0x14
+    0xE581_0000, // 0C: str r0, [r1]
+    0xEAFF_FFFB, // 10: b 0x04
+    0xE282_2001, // 14: add r2, r2, #1
+    0xE12F_FF1E, // 18: bx lr
+];
+
+const THUMB: u32 = CODE + 0x100;
+const THUMB_CODE: [u16; 2] = [
+    0x3001, // 00: adds r0, #1
+    0xE7FD, // 02: b 0x00
+];
+
+fn arm_program() -> Gba {
+    let mut gba = boot(ROM);
+    for (i, &instr) in ARM.iter().enumerate() {
+        gba.bus.write::<u32>(CODE + i as u32 * 4, instr);
+    }
+    gba.cpu.regs.set_reg_i(1, DATA);
+    gba.cpu.regs.set_reg_i(2, 0);
+    gba.cpu.set_next_pc(&gba.bus, CODE);
+    gba
+}
+
+fn thumb_program() -> Gba {
+    let mut gba = boot(ROM);
+    for (i, &instr) in THUMB_CODE.iter().enumerate() {
+        gba.bus.write::<u16>(THUMB + i as u32 * 2, instr);
+    }
+    gba.cpu.regs.set_t(true);
+    gba.cpu.set_next_pc(&gba.bus, THUMB);
+    gba
+}
+
+fn run(gba: &mut Gba) -> StopReason {
+    gba.run(CLOCKS_PER_FRAME)
+}
+
+fn reg(gba: &Gba, n: u32) -> u32 {
+    gba.cpu.regs.get_reg_i(n)
+}
+
+#[test]
+fn breakpoint_stops_before_the_instruction() {
+    let mut gba = arm_program();
+    gba.add_breakpoint(Breakpoint::new(CODE + 0x14));
+    assert_eq!(run(&mut gba), StopReason::Breakpoint(CODE + 0x14));
+    assert_eq!(gba.cpu.next_pc(), CODE + 0x14);
+    assert_eq!(reg(&gba, 2), 0);
+}
+
+#[test]
+fn breakpoint_rearms_after_resuming() {
+    let mut gba = arm_program();
+    gba.add_breakpoint(Breakpoint::new(CODE + 0x14));
+    for calls in 0..3 {
+        assert_eq!(run(&mut gba), StopReason::Breakpoint(CODE + 0x14));
+        assert_eq!(reg(&gba, 2), calls);
+    }
+
+    // Without breakpoints the interrupted frame runs to its end
+    gba.clear_breakpoints();
+    assert_eq!(run(&mut gba), StopReason::FrameEnd);
+}
+
+#[test]
+fn conditional_breakpoint() {
+    let mut gba = arm_program();
+    gba.add_breakpoint(Breakpoint {
+        condition: Some(Condition {
+            reg: Reg::R0,
+            cmp: Comparison::Eq,
+            value: 5,
+        }),
+        ..Breakpoint::new(CODE + 0x08)
+    });
+    assert_eq!(run(&mut gba), StopReason::Breakpoint(CODE + 0x08));
+    assert_eq!(reg(&gba, 0), 5);
+}
+
+#[test]
+fn breakpoint_in_thumb_state() {
+    // Bit 0 of Thumb addresses is ignored
+    let mut gba = thumb_program();
+    gba.add_breakpoint(Breakpoint {
+        thumb: Some(true),
+        ..Breakpoint::new(THUMB + 3)
+    });
+    assert_eq!(run(&mut gba), StopReason::Breakpoint(THUMB + 3));
+    assert_eq!(gba.cpu.next_pc(), THUMB + 2);
+
+    // An ARM only breakpoint is never hit by Thumb code
+    let mut gba = thumb_program();
+    gba.add_breakpoint(Breakpoint {
+        thumb: Some(false),
+        ..Breakpoint::new(THUMB + 2)
+    });
+    assert_eq!(run(&mut gba), StopReason::FrameEnd);
+}
+
+#[test]
+fn step_into_call() {
+    let mut gba = arm_program();
+    gba.add_breakpoint(Breakpoint::new(CODE + 0x08));
+    run(&mut gba);
+    gba.clear_breakpoints();
+
+    gba.step(Step::Into);
+    assert_eq!(run(&mut gba), StopReason::Step);
+    assert_eq!(gba.cpu.next_pc(), CODE + 0x14);
+}
+
+#[test]
+fn step_over_call() {
+    let mut gba = arm_program();
+    gba.add_breakpoint(Breakpoint::new(CODE + 0x08));
+    run(&mut gba);
+    gba.clear_breakpoints();
+
+    gba.step(Step::Over);
+    assert_eq!(run(&mut gba), StopReason::Step);
+    assert_eq!(gba.cpu.next_pc(), CODE + 0x0C);
+    assert_eq!(reg(&gba, 2), 1);
+}
+
+#[test]
+fn step_out_of_call() {
+    let mut gba = arm_program();
+    gba.add_breakpoint(Breakpoint::new(CODE + 0x14));
+    run(&mut gba);
+    gba.clear_breakpoints();
+
+    gba.step(Step::Out);
+    assert_eq!(run(&mut gba), StopReason::Step);
+    assert_eq!(gba.cpu.next_pc(), CODE + 0x0C);
+    assert_eq!(reg(&gba, 2), 1);
+}
+
+#[test]
+fn write_watchpoint() {
+    let mut gba = arm_program();
+    let watchpoint = Watchpoint {
+        addr: DATA + 2,
+        len: 1,
+        kind: WatchKind::Write,
+    };
+    gba.add_watchpoint(watchpoint);
+
+    // Stops after the store, the word access overlaps the watched byte
+    let hit = WatchHit {
+        watchpoint,
+        addr: DATA,
+        kind: WatchKind::Write,
+    };
+    assert_eq!(run(&mut gba), StopReason::Watchpoint(hit));
+    assert_eq!(gba.cpu.next_pc(), CODE + 0x10);
+    assert_eq!(reg(&gba, 0), 1);
+
+    assert_eq!(run(&mut gba), StopReason::Watchpoint(hit));
+    assert_eq!(reg(&gba, 0), 2);
+}
+
+#[test]
+fn read_watchpoint_ignores_writes() {
+    let mut gba = arm_program();
+    gba.add_watchpoint(Watchpoint {
+        addr: DATA,
+        len: 4,
+        kind: WatchKind::Read,
+    });
+    assert_eq!(run(&mut gba), StopReason::FrameEnd);
+}
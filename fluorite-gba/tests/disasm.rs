@@ -0,0 +1,175 @@
+//! Disassembles known encodings of every ARM and Thumb instruction group. All
+//! instructions are located at the start of the rom.
+
+use fluorite_gba::arm::disasm::{disasm_arm, disasm_thumb};
+
+const ADDR: u32 = 0x0800_0000;
+
+const ARM: &[(u32, &str)] = &[
+    // Branch and exchange
+    (0xE12FFF1E, "bx lr"),
+    // Branch and branch with link
+    (0xEA00002E, "b 0x080000c0"),
+    (0xEAFFFFFE, "b 0x08000000"),
+    (0xEB000000, "bl 0x08000008"),
+    (0x0AFFFFFE, "beq 0x08000000"),
+    // Data processing
+    (0xE1A00000, "mov r0, r0"),
+    (0xE3A00001, "mov r0, #1"),
+    (0x13A00000, "movne r0, #0"),
+    (0xE3A004FF, "mov r0, #0xff000000"),
+    (0xE2800C01, "add r0, r0, #0x100"),
+    (0xE0910002, "adds r0, r1, r2"),
+    (0xE1A01102, "mov r1, r2, lsl #2"),
+    (0xE1A01062, "mov r1, r2, rrx"),
+    (0xE1A01022, "mov r1, r2, lsr #32"),
+    (0xE1A01332, "mov r1, r2, lsr r3"),
+    (0xE3500000, "cmp r0, #0"),
+    (0xE1E01002, "mvn r1, r2"),
+    (0xE28F0004, "adr r0, 0x0800000c"),
+    (0xE24F0008, "adr r0, 0x08000000"),
+    // PSR transfer
+    (0xE10F0000, "mrs r0, cpsr"),
+    (0xE14F0000, "mrs r0, spsr"),
+    (0xE129F000, "msr cpsr_fc, r0"),
+    (0xE328F20F, "msr cpsr_f, #0xf0000000"),
+    // Multiply and multiply long
+    (0xE0000291, "mul r0, r1, r2"),
+    (0xE0203291, "mla r0, r1, r2, r3"),
+    (0xE0810392, "umull r0, r1, r2, r3"),
+    (0xE0F10392, "smlals r0, r1, r2, r3"),
+    // Single data swap
+    (0xE1020091, "swp r0, r1, [r2]"),
+    (0xE1420091, "swpb r0, r1, [r2]"),
+    // Single data transfer
+    (0xE5910004, "ldr r0, [r1, #4]"),
+    (0xE5910000, "ldr r0, [r1]"),
+    (0xE4910004, "ldr r0, [r1], #4"),
+    (0xE5B10004, "ldr r0, [r1, #4]!"),
+    (0xE5510001, "ldrb r0, [r1, #-1]"),
+    (0xE7910102, "ldr r0, [r1, r2, lsl #2]"),
+    (0xE6A10002, "strt r0, [r1], r2"),
+    (0xE59F0010, "ldr r0, [pc, #0x10] ; 0x08000018"),
+    (0xE51F0004, "ldr r0, [pc, #-4] ; 0x08000004"),
+    // Halfword and signed data transfer
+    (0xE1D100B2, "ldrh r0, [r1, #2]"),
+    (0xE19100D2, "ldrsb r0, [r1, r2]"),
+    (0xE1DF00B4, "ldrh r0, [pc, #4] ; 0x0800000c"),
+    // Block data transfer
+    (0xE92D4010, "push {r4, lr}"),
+    (0xE8BD8010, "pop {r4, pc}"),
+    (0xE8900007, "ldm r0, {r0-r2}"),
+    (0xE9A0000F, "stmib r0!, {r0-r3}"),
+    (0xE8D08000, "ldm r0, {pc}^"),
+    (0xE9100003, "ldmdb r0, {r0, r1}"),
+    // Software interrupt
+    (0xEF000000, "svc #0"),
+    (0xEF060000, "svc #0x60000"),
+    // Coprocessor
+    (0xED910100, "ldc p1, c0, [r1]"),
+    (0xEE010F10, "mcr p15, #0, r0, c1, c0, #0"),
+    (0xEE210102, "cdp p1, #2, c0, c1, c2, #0"),
+    // Undefined
+    (0xE6000010, "undefined"),
+    (0xE1900090, "undefined"),
+];
+
+const THUMB: &[(u32, &str)] = &[
+    // Move shifted register
+    (0x0008, "movs r0, r1"),
+    (0x0088, "lsls r0, r1, #2"),
+    (0x0808, "lsrs r0, r1, #32"),
+    (0x1048, "asrs r0, r1, #1"),
+    // Add/subtract
+    (0x1888, "adds r0, r1, r2"),
+    (0x1E48, "subs r0, r1, #1"),
+    // Move/compare/add/subtract immediate
+    (0x2001, "movs r0, #1"),
+    (0x29FF, "cmp r1, #0xff"),
+    // ALU operations
+    (0x4008, "ands r0, r1"),
+    (0x4248, "negs r0, r1"),
+    (0x4348, "muls r0, r1, r0"),
+    (0x43C8, "mvns r0, r1"),
+    // Hi register operations and branch exchange
+    (0x4770, "bx lr"),
+    (0x46C0, "mov r8, r8"),
+    (0x4485, "add sp, r0"),
+    // PC-relative load
+    (0x4801, "ldr r0, [pc, #4] ; 0x08000008"),
+    // Load/store with register offset
+    (0x5888, "ldr r0, [r1, r2]"),
+    (0x5488, "strb r0, [r1, r2]"),
+    // Load/store sign-extended byte/halfword
+    (0x5E88, "ldrsh r0, [r1, r2]"),
+    (0x5288, "strh r0, [r1, r2]"),
+    // Load/store with immediate offset
+    (0x6848, "ldr r0, [r1, #4]"),
+    (0x7848, "ldrb r0, [r1, #1]"),
+    (0x6008, "str r0, [r1]"),
+    // Load/store halfword
+    (0x8848, "ldrh r0, [r1, #2]"),
+    // SP-relative load/store
+    (0x9801, "ldr r0, [sp, #4]"),
+    // Load address
+    (0xA001, "adr r0, 0x08000008"),
+    (0xA801, "add r0, sp, #4"),
+    // Add offset to stack pointer
+    (0xB082, "sub sp, #8"),
+    (0xB002, "add sp, #8"),
+    // Push/pop registers
+    (0xB510, "push {r4, lr}"),
+    (0xBD10, "pop {r4, pc}"),
+    (0xB40F, "push {r0-r3}"),
+    // Multiple load/store
+    (0xC80E, "ldmia r0!, {r1-r3}"),
+    (0xC001, "stmia r0!, {r0}"),
+    // Conditional branch
+    (0xD0FE, "beq 0x08000000"),
+    (0xDC02, "bgt 0x08000008"),
+    // Software interrupt
+    (0xDF05, "svc #5"),
+    // Unconditional branch
+    (0xE7FE, "b 0x08000000"),
+    (0xE002, "b 0x08000008"),
+    // Long branch with link, with the second half in the upper bits
+    (0xF800F000, "bl 0x08000004"),
+    (0xFFFEF7FF, "bl 0x08000000"),
+    (0xF001, "add lr, pc, #0x1000"),
+    (0xF7FF, "sub lr, pc, #0x1000"),
+    (0xF801, "bl lr, #2"),
+    // Undefined
+    (0xDE00, "undefined"),
+    (0xB100, "undefined"),
+    (0xE800, "undefined"),
+];
+
+fn check(table: &[(u32, &str)], disasm: fn(u32, u32) -> String) {
+    let mismatches: Vec<String> = table
+        .iter()
+        .filter_map(|&(opcode, expected)| {
+            let text = disasm(ADDR, opcode);
+            (text != expected).then(|| format!("{opcode:08X}: expected {expected}, got {text}"))
+        })
+        .collect();
+    assert!(mismatches.is_empty(), "\n{}", mismatches.join("\n"));
+}
+
+#[test]
+fn arm() {
+    check(ARM, disasm_arm);
+}
+
+#[test]
+fn thumb() {
+    check(THUMB, disasm_thumb);
+}
+
+#[test]
+fn thumb_literal_is_word_aligned() {
+    assert_eq!(
+        disasm_thumb(ADDR + 2, 0x4800),
+        "ldr r0, [pc, #0] ; 0x08000004"
+    );
+    assert_eq!(disasm_thumb(ADDR + 2, 0xA001), "adr r0, 0x08000008");
+}
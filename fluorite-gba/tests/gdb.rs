@@ -0,0 +1,139 @@
+//! Talks to `GdbStub` over TCP like GDB would. The stub runs on the test thread, so every
+//! request is written before the stub gets to run.
+
+mod common;
+
+use common::{boot, rom};
+use fluorite_gba::{consts::CLOCKS_PER_FRAME, gba::Gba, gdb::GdbStub};
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+const ROM: &str = "tonc/first.gba";
+
+struct Client {
+    gdb: GdbStub,
+    gba: Gba,
+    stream: TcpStream,
+    input: Vec<u8>,
+}
+
+impl Client {
+    fn connect() -> Client {
+        let gdb = GdbStub::bind(0).unwrap();
+        let stream = TcpStream::connect(gdb.local_addr().unwrap()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(1)))
+            .unwrap();
+        Client {
+            gdb,
+            gba: boot(ROM),
+            stream,
+            input: Vec::new(),
+        }
+    }
+
+    fn send(&mut self, packet: &str) {
+        let checksum = packet.bytes().fold(0u8, |sum, c| sum.wrapping_add(c));
+        write!(self.stream, "${packet}#{checksum:02x}").unwrap();
+    }
+
+    /// Lets the stub run until it replied with a packet
+    fn reply(&mut self) -> String {
+        for _ in 0..100 {
+            self.gdb.run(&mut self.gba, CLOCKS_PER_FRAME);
+            let mut buffer = [0; 0x1000];
+            match self.stream.read(&mut buffer) {
+                Ok(len) => self.input.extend_from_slice(&buffer[..len]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => (),
+                Err(e) => panic!("{e}"),
+            }
+            // Skip the acks
+            while self.input.first() == Some(&b'+') {
+                self.input.remove(0);
+            }
+            let end = match self.input.iter().position(|&c| c == b'#') {
+                Some(end) if end + 2 < self.input.len() => end,
+                _ => continue,
+            };
+            assert_eq!(self.input[0], b'$');
+            let packet: Vec<u8> = self.input.drain(..end + 3).collect();
+            return String::from_utf8(packet[1..end].to_vec()).unwrap();
+        }
+        panic!("no reply from the stub");
+    }
+
+    fn request(&mut self, packet: &str) -> String {
+        self.send(packet);
+        self.reply()
+    }
+}
+
+#[test]
+fn stops_on_attach() {
+    let mut client = Client::connect();
+    assert_eq!(client.request("?"), "S05");
+    assert!(client.gdb.is_stopped());
+}
+
+#[test]
+fn registers() {
+    let mut client = Client::connect();
+    let registers = client.request("g");
+    // r0-r15, the CPSR and the banked registers, as little endian words
+    assert_eq!(registers.len(), (17 + 27) * 8);
+    assert_eq!(&registers[15 * 8..16 * 8], "00000008");
+    assert_eq!(client.request("p0f"), "00000008");
+}
+
+#[test]
+fn memory() {
+    let mut client = Client::connect();
+    let expected: String = std::fs::read(rom(ROM)).unwrap()[..4]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    assert_eq!(client.request("m08000000,4"), expected);
+
+    assert_eq!(client.request("M02000000,2:abcd"), "OK");
+    assert_eq!(client.request("m02000000,2"), "abcd");
+}
+
+#[test]
+fn breakpoints() {
+    let mut client = Client::connect();
+    assert_eq!(client.request("Z0,08000100,4"), "OK");
+    assert_eq!(client.request("z0,08000100,4"), "OK");
+    // Only breakpoints that were set can be removed
+    assert_eq!(client.request("z0,08000100,4"), "E01");
+}
+
+#[test]
+fn continue_until_hardware_breakpoint() {
+    let mut client = Client::connect();
+    // The rom header branches to 0x080000C0
+    assert_eq!(client.request("Z1,080000c0,2"), "OK");
+    client.send("c");
+    assert_eq!(client.reply(), "S05");
+    assert_eq!(client.request("p0f"), "c0000008");
+
+    assert_eq!(client.request("z1,080000c0,2"), "OK");
+    assert_eq!(client.request("z1,080000c0,2"), "E01");
+}
+
+#[test]
+fn continue_until_watchpoint() {
+    let mut client = Client::connect();
+    // The rom sets up the display control register first thing
+    assert_eq!(client.request("Z2,04000000,4"), "OK");
+    client.send("c");
+    let reply = client.reply();
+    assert!(
+        reply.starts_with("T05watch:04000000"),
+        "unexpected stop reply {reply}"
+    );
+    assert!(client.gdb.is_stopped());
+    assert_eq!(client.request("?"), reply);
+}
@@ -0,0 +1,242 @@
+//! Runs the BIOS functions the built-in BIOS emulates and compares them against results
+//! of the real BIOS. The same tables are checked against the BIOS dump in `roms/`.
+
+mod common;
+
+use common::{boot, boot_with_bios};
+use fluorite_gba::gba::Gba;
+
+const ROM: &str = "tonc/first.gba";
+
+// The SWI is run from IWRAM, input data is placed in EWRAM
+const CODE: u32 = 0x0300_0000;
+const SRC: u32 = 0x0200_0000;
+const DEST: u32 = 0x0200_1000;
+
+fn emulators() -> [(&'static str, Gba); 2] {
+    [("hle", boot(ROM)), ("bios", boot_with_bios(ROM))]
+}
+
+/// Calls the BIOS function `comment` from ARM state and returns r0-r3
+fn swi(gba: &mut Gba, comment: u8, args: [u32; 4]) -> [u32; 4] {
+    gba.bus
+        .write::<u32>(CODE, 0xEF00_0000 | (comment as u32) << 16);
+    // b .
+    gba.bus.write::<u32>(CODE + 4, 0xEAFF_FFFE);
+    for (i, arg) in args.into_iter().enumerate() {
+        gba.cpu.regs.set_reg_i(i as u32, arg);
+    }
+    gba.cpu.set_next_pc(&gba.bus, CODE);
+
+    gba.cpu.emulate_instr(&mut gba.bus);
+    for _ in 0..1_000_000 {
+        if gba.cpu.next_pc() == CODE + 4 {
+            return [0, 1, 2, 3].map(|i| gba.cpu.regs.get_reg_i(i));
+        }
+        gba.cpu.emulate_instr(&mut gba.bus);
+    }
+    panic!("SWI 0x{comment:02X} didn't return");
+}
+
+fn write(gba: &mut Gba, addr: u32, data: &[u8]) {
+    for (i, &byte) in data.iter().enumerate() {
+        gba.bus.write::<u8>(addr + i as u32, byte);
+    }
+}
+
+fn read(gba: &Gba, addr: u32, len: usize) -> Vec<u8> {
+    (0..len as u32)
+        .map(|i| gba.bus.read::<u8>(addr + i))
+        .collect()
+}
+
+// Checks each `(args, results)` row on both emulators
+fn check(comment: u8, table: &[([u32; 4], [Option<u32>; 4])]) {
+    for (name, mut gba) in emulators() {
+        for (args, expected) in table {
+            let result = swi(&mut gba, comment, *args);
+            for (i, (&result, expected)) in result.iter().zip(expected).enumerate() {
+                if let Some(expected) = *expected {
+                    assert_eq!(
+                        result, expected,
+                        "{name}: SWI 0x{comment:02X} with {args:X?} returned {result:#X} in r{i}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+// Decompresses `data` from EWRAM and compares the written bytes
+fn check_decompress(comment: u8, data: &[u8], expected: &[u8]) {
+    for (name, mut gba) in emulators() {
+        write(&mut gba, SRC, data);
+        write(&mut gba, DEST, &[0; 0x40]);
+        swi(&mut gba, comment, [SRC, DEST, 0, 0]);
+        assert_eq!(
+            read(&gba, DEST, expected.len()),
+            expected,
+            "{name}: SWI 0x{comment:02X}"
+        );
+    }
+}
+
+#[test]
+fn div() {
+    let row = |num: i32, denom: i32, quot: i32, rem: i32| {
+        let abs = quot.unsigned_abs();
+        (
+            [num as u32, denom as u32, 0, 0],
+            [Some(quot as u32), Some(rem as u32), None, Some(abs)],
+        )
+    };
+    check(
+        0x06,
+        &[
+            row(7, 2, 3, 1),
+            row(-7, 2, -3, -1),
+            row(7, -2, -3, 1),
+            row(-7, -2, 3, -1),
+            row(100, 7, 14, 2),
+            row(i32::MIN, -1, i32::MIN, 0),
+        ],
+    );
+}
+
+#[test]
+fn sqrt() {
+    let row = |n: u32, root: u32| ([n, 0, 0, 0], [Some(root), None, None, None]);
+    check(
+        0x08,
+        &[
+            row(0, 0),
+            row(1, 1),
+            row(2, 1),
+            row(4, 2),
+            row(99, 9),
+            row(0x10000, 0x100),
+            row(0x4000_0000, 0x8000),
+            row(0xFFFF_FFFF, 0xFFFF),
+        ],
+    );
+}
+
+#[test]
+fn arctan() {
+    let row = |tan: i32, angle: i32| {
+        (
+            [tan as u32, 0, 0, 0],
+            [Some(angle as u32), None, None, None],
+        )
+    };
+    check(
+        0x09,
+        &[
+            row(0, 0),
+            row(0x1000, 0x9FB),
+            row(0x2000, 0x12E4),
+            row(0x3000, 0x1A37),
+            row(0x4000, 0x2000),
+            row(-0x2000, -0x12E4),
+            row(-0x4000, -0x2000),
+        ],
+    );
+}
+
+#[test]
+fn arctan2() {
+    let row =
+        |x: i32, y: i32, angle: u32| ([x as u32, y as u32, 0, 0], [Some(angle), None, None, None]);
+    check(
+        0x0A,
+        &[
+            row(1, 0, 0),
+            row(0, 1, 0x4000),
+            row(-1, 0, 0x8000),
+            row(0, -1, 0xC000),
+            row(0x100, 0x100, 0x2000),
+            row(-0x100, 0x100, 0x6000),
+            row(-0x100, -0x100, 0xA000),
+            row(0x100, -0x100, 0xE000),
+            row(0x4000, 0x1000, 0x9FB),
+            row(0x1000, 0x4000, 0x3605),
+            row(-0x3000, 0x1000, 0x72E4),
+            row(0x1234, -0x4321, 0xCACA),
+        ],
+    );
+}
+
+#[test]
+fn bg_affine_set() {
+    // Origin, center, scale and angle in, the matrix and the start of the first line out
+    let table: &[((i32, i32, i16, i16, i16, i16, u16), ([u16; 4], i32, i32))] = &[
+        (
+            (0x1000, 0x2000, 0x10, 0x20, 0x100, 0x100, 0),
+            ([0x100, 0, 0, 0x100], 0, 0),
+        ),
+        (
+            (0x8000, 0x4000, 120, 80, 0x200, 0x80, 0x4000),
+            ([0, 0xFE00, 0x80, 0], 0x12000, 0x400),
+        ),
+        (
+            (-0x100, 0x300, -8, 16, -0x100, 0x180, 0xA000),
+            ([0xB5, 0xFF4B, 0xFEF0, 0xFEF0], 0xFF8, 0xB80),
+        ),
+    ];
+    for (name, mut gba) in emulators() {
+        for &((ox, oy, cx, cy, sx, sy, angle), (params, x, y)) in table {
+            let mut src = Vec::new();
+            src.extend(ox.to_le_bytes());
+            src.extend(oy.to_le_bytes());
+            for half in [cx, cy, sx, sy] {
+                src.extend(half.to_le_bytes());
+            }
+            src.extend(angle.to_le_bytes());
+            write(&mut gba, SRC, &src);
+            swi(&mut gba, 0x0E, [SRC, DEST, 1, 0]);
+
+            let mut expected = Vec::new();
+            for param in params {
+                expected.extend(param.to_le_bytes());
+            }
+            expected.extend(x.to_le_bytes());
+            expected.extend(y.to_le_bytes());
+            assert_eq!(read(&gba, DEST, 16), expected, "{name}: angle {angle:#X}");
+        }
+    }
+}
+
+#[test]
+fn lz77() {
+    // Three literals, then nine bytes copied from three back
+    let data = [0x10, 12, 0, 0, 0x10, b'A', b'B', b'C', 0x60, 0x02];
+    check_decompress(0x11, &data, b"ABCABCABCABC");
+}
+
+#[test]
+fn huffman() {
+    // A root with the leaves A and B, followed by one word of bits, most significant first
+    let data = [
+        0x28, 8, 0, 0, 0x01, 0xC0, b'A', b'B', 0x00, 0x00, 0x00, 0x63,
+    ];
+    check_decompress(0x13, &data, b"ABBAAABB");
+}
+
+#[test]
+fn run_length() {
+    // A run of five A and three literals
+    let data = [0x30, 8, 0, 0, 0x82, b'A', 0x02, b'x', b'y', b'z'];
+    check_decompress(0x14, &data, b"AAAAAxyz");
+}
+
+#[test]
+fn diff_8bit() {
+    let data = [0x81, 4, 0, 0, 1, 1, 1, 0xFE];
+    check_decompress(0x16, &data, &[1, 2, 3, 1]);
+}
+
+#[test]
+fn diff_16bit() {
+    let data = [0x82, 6, 0, 0, 0x00, 0x01, 0x00, 0x01, 0xFF, 0xFF];
+    check_decompress(0x18, &data, &[0x00, 0x01, 0x00, 0x02, 0xFF, 0x01]);
+}
@@ -0,0 +1,75 @@
+//! Requests the keypad interrupt from KEYCNT like hardware does, whenever the condition
+//! holds while KEYINPUT changes or KEYCNT is written.
+
+mod common;
+
+use common::{boot, run_frames};
+use fluorite_gba::{gba::Gba, io::keypad::KeyState};
+
+// Doesn't enable interrupts, so IF is left alone
+const ROM: &str = "tonc/first.gba";
+
+const KEYCNT: u32 = 0x0400_0132;
+const IF: u32 = 0x0400_0202;
+const KEYPAD: u16 = 1 << 12;
+
+// Interrupt when A and B are both held
+const A_AND_B: u16 = 0xC003;
+
+fn requested(gba: &Gba) -> bool {
+    gba.bus.read::<u16>(IF) & KEYPAD != 0
+}
+
+fn acknowledge(gba: &mut Gba) {
+    gba.bus.write::<u16>(IF, KEYPAD);
+    assert!(!requested(gba));
+}
+
+#[test]
+fn requested_when_keys_match() {
+    let mut gba = boot(ROM);
+    gba.bus.write::<u16>(KEYCNT, A_AND_B);
+    gba.press(KeyState::A);
+    assert!(!requested(&gba));
+    gba.press(KeyState::B);
+    assert!(requested(&gba));
+}
+
+#[test]
+fn any_key() {
+    let mut gba = boot(ROM);
+    gba.bus.write::<u16>(KEYCNT, A_AND_B & !0x8000);
+    gba.press(KeyState::B);
+    assert!(requested(&gba));
+}
+
+#[test]
+fn held_keys_request_again_after_acknowledging() {
+    let mut gba = boot(ROM);
+    gba.bus.write::<u16>(KEYCNT, A_AND_B);
+    gba.press(KeyState::A | KeyState::B);
+    assert!(requested(&gba));
+    acknowledge(&mut gba);
+
+    // Nothing changed, holding the keys doesn't request it on its own
+    gba.press(KeyState::A);
+    run_frames(&mut gba, 2);
+    assert!(!requested(&gba));
+
+    // Writing KEYCNT checks the condition again
+    gba.bus.write::<u16>(KEYCNT, A_AND_B);
+    assert!(requested(&gba));
+    acknowledge(&mut gba);
+
+    // So does any other key changing while the combination is held
+    gba.press(KeyState::START);
+    assert!(requested(&gba));
+}
+
+#[test]
+fn disabled() {
+    let mut gba = boot(ROM);
+    gba.bus.write::<u16>(KEYCNT, A_AND_B & !0x4000);
+    gba.press(KeyState::A | KeyState::B);
+    assert!(!requested(&gba));
+}
@@ -0,0 +1,164 @@
+//! Transfers between two emulators connected by a link cable. The roms don't touch the
+//! serial port, the registers are programmed from outside instead.
+
+mod common;
+
+use common::{boot, run_frames};
+use fluorite_gba::{
+    gba::Gba,
+    io::sio::{LinkAddr, Loopback, SocketLink},
+};
+use std::{net::TcpListener, thread, time::Duration};
+
+const SIODATA32: u32 = 0x04000120;
+const SIOMULTI: u32 = 0x04000120;
+const SIOCNT: u32 = 0x04000128;
+const SIOMLT_SEND: u32 = 0x0400012A;
+const IF: u32 = 0x04000202;
+const SERIAL_IRQ: u16 = 1 << 7;
+
+// Doesn't enable interrupts, so requested ones stay in IF
+const ROM: &str = "tonc/first.gba";
+
+fn connected_pair() -> (Gba, Gba) {
+    let (a, b) = Loopback::pair();
+    let mut parent = boot(ROM);
+    let mut child = boot(ROM);
+    parent.set_link(Box::new(a));
+    child.set_link(Box::new(b));
+    (parent, child)
+}
+
+fn serial_irq(gba: &Gba) -> bool {
+    gba.bus.read::<u16>(IF) & SERIAL_IRQ != 0
+}
+
+#[test]
+fn normal_32bit() {
+    let (mut parent, mut child) = connected_pair();
+
+    // 32 bit mode with irq, the child waits for the external clock
+    child.bus.write::<u32>(SIODATA32, 0x2222_2222);
+    child.bus.write::<u16>(SIOCNT, 0x5080);
+    parent.bus.write::<u32>(SIODATA32, 0x1111_1111);
+    parent.bus.write::<u16>(SIOCNT, 0x5081);
+
+    run_frames(&mut parent, 1);
+    run_frames(&mut child, 1);
+
+    assert_eq!(parent.bus.read::<u32>(SIODATA32), 0x2222_2222);
+    assert_eq!(child.bus.read::<u32>(SIODATA32), 0x1111_1111);
+    for gba in [&parent, &child] {
+        assert!(serial_irq(gba));
+        assert_eq!(gba.bus.read::<u16>(SIOCNT) & 0x80, 0, "start bit still set");
+    }
+}
+
+#[test]
+fn multiplayer() {
+    let (mut parent, mut child) = connected_pair();
+
+    child.bus.write::<u16>(SIOMLT_SEND, 0x2222);
+    child.bus.write::<u16>(SIOCNT, 0x6000);
+    parent.bus.write::<u16>(SIOMLT_SEND, 0x1111);
+    parent.bus.write::<u16>(SIOCNT, 0x6080);
+
+    run_frames(&mut parent, 1);
+    run_frames(&mut child, 1);
+
+    for gba in [&parent, &child] {
+        let multi: Vec<u16> = (0..4)
+            .map(|i| gba.bus.read::<u16>(SIOMULTI + 2 * i))
+            .collect();
+        assert_eq!(multi, [0x1111, 0x2222, 0xFFFF, 0xFFFF]);
+        assert!(serial_irq(gba));
+    }
+    // The multiplayer id is in bits 4-5
+    assert_eq!(parent.bus.read::<u16>(SIOCNT) >> 4 & 3, 0);
+    assert_eq!(child.bus.read::<u16>(SIOCNT) >> 4 & 3, 1);
+}
+
+// Runs the parent and the child in their own thread, connected through a socket on localhost
+fn over_sockets<R: Send + 'static>(
+    parent: impl FnOnce(&mut Gba) -> R + Send + 'static,
+    child: impl FnOnce(&mut Gba) -> R + Send + 'static,
+) -> (R, R) {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let addr = LinkAddr::Tcp(format!("127.0.0.1:{port}"));
+
+    let host_addr = addr.clone();
+    let parent = thread::spawn(move || {
+        let link = SocketLink::host(&host_addr, 2).unwrap();
+        let mut gba = boot(ROM);
+        gba.set_link(Box::new(link));
+        parent(&mut gba)
+    });
+    let child = thread::spawn(move || {
+        // The host may not be listening yet
+        let link = (0..100)
+            .find_map(|_| {
+                SocketLink::join(&addr)
+                    .map_err(|_| thread::sleep(Duration::from_millis(20)))
+                    .ok()
+            })
+            .expect("failed to join the host");
+        let mut gba = boot(ROM);
+        gba.set_link(Box::new(link));
+        child(&mut gba)
+    });
+    (parent.join().unwrap(), child.join().unwrap())
+}
+
+// The data of the other unit only arrives when the cable is polled, so the parent waits
+// a frame before starting a transfer
+#[test]
+fn normal_32bit_over_sockets() {
+    let (parent, child) = over_sockets(
+        |gba| {
+            run_frames(gba, 1);
+            gba.bus.write::<u32>(SIODATA32, 0x1111_1111);
+            gba.bus.write::<u16>(SIOCNT, 0x5081);
+            run_frames(gba, 4);
+            (gba.bus.read::<u32>(SIODATA32), serial_irq(gba))
+        },
+        |gba| {
+            gba.bus.write::<u32>(SIODATA32, 0x2222_2222);
+            gba.bus.write::<u16>(SIOCNT, 0x5080);
+            run_frames(gba, 5);
+            (gba.bus.read::<u32>(SIODATA32), serial_irq(gba))
+        },
+    );
+    assert_eq!(parent, (0x2222_2222, true));
+    assert_eq!(child, (0x1111_1111, true));
+}
+
+#[test]
+fn multiplayer_over_sockets() {
+    let multi = |gba: &Gba| -> Vec<u16> {
+        (0..4)
+            .map(|i| gba.bus.read::<u16>(SIOMULTI + 2 * i))
+            .collect()
+    };
+    let (parent, child) = over_sockets(
+        move |gba| {
+            run_frames(gba, 1);
+            gba.bus.write::<u16>(SIOMLT_SEND, 0x1111);
+            gba.bus.write::<u16>(SIOCNT, 0x6080);
+            run_frames(gba, 4);
+            (multi(gba), serial_irq(gba))
+        },
+        move |gba| {
+            gba.bus.write::<u16>(SIOMLT_SEND, 0x2222);
+            gba.bus.write::<u16>(SIOCNT, 0x6000);
+            run_frames(gba, 5);
+            (multi(gba), serial_irq(gba))
+        },
+    );
+    let expected = (vec![0x1111, 0x2222, 0xFFFF, 0xFFFF], true);
+    assert_eq!(parent, expected);
+    assert_eq!(child, expected);
+}
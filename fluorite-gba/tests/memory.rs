@@ -0,0 +1,78 @@
+//! Checks how the bus maps the cartridge rom and backup regions.
+
+mod common;
+
+use common::boot;
+use fluorite_gba::gba::Gba;
+
+// Has no save type string, so it gets SRAM
+const ROM: &str = "tonc/first.gba";
+
+fn sram() -> Gba {
+    let mut gba = boot(ROM);
+    gba.bus.write::<u8>(0x0E00_0010, 0xAB);
+    gba.bus.write::<u8>(0x0E00_0011, 0xCD);
+    gba
+}
+
+#[test]
+fn rom_writes_are_ignored() {
+    let mut gba = boot(ROM);
+    for addr in [0x0800_0100, 0x0A00_0100, 0x0C00_0100] {
+        let value = gba.bus.read::<u32>(addr);
+        gba.bus.write::<u32>(addr, !value);
+        gba.bus.write::<u16>(addr, !value as u16);
+        gba.bus.write::<u8>(addr, !value as u8);
+        assert_eq!(gba.bus.read::<u32>(addr), value, "0x{addr:08X}");
+    }
+}
+
+#[test]
+fn rom_is_mirrored() {
+    let gba = boot(ROM);
+    let value = gba.bus.read::<u32>(0x0800_0100);
+    assert_eq!(gba.bus.read::<u32>(0x0A00_0100), value);
+    assert_eq!(gba.bus.read::<u32>(0x0C00_0100), value);
+}
+
+#[test]
+fn open_bus_past_rom_end() {
+    // The halfword address of 0x09FFFFF0 is 0xFFFFF8, its lower 16 bits are read back
+    let gba = boot(ROM);
+    assert_eq!(gba.bus.read::<u32>(0x09FF_FFF0), 0xFFF9_FFF8);
+    assert_eq!(gba.bus.read::<u16>(0x09FF_FFF2), 0xFFF9);
+    assert_eq!(gba.bus.read::<u8>(0x09FF_FFF0), 0xF8);
+    assert_eq!(gba.bus.read::<u8>(0x09FF_FFF1), 0xFF);
+}
+
+#[test]
+fn sram_is_mirrored() {
+    let gba = sram();
+    for addr in [
+        0x0E00_0010,
+        0x0E00_8010,
+        0x0E01_0010,
+        0x0F00_0010,
+        0x0F00_8010,
+    ] {
+        assert_eq!(gba.bus.read::<u8>(addr), 0xAB, "0x{addr:08X}");
+    }
+}
+
+#[test]
+fn sram_wide_reads_repeat_the_byte() {
+    let gba = sram();
+    assert_eq!(gba.bus.read::<u16>(0x0E00_0010), 0xABAB);
+    assert_eq!(gba.bus.read::<u32>(0x0E00_0010), 0xABAB_ABAB);
+    assert_eq!(gba.bus.read::<u16>(0x0E00_0011), 0xCDCD);
+    assert_eq!(gba.bus.read::<u32>(0x0F00_8011), 0xCDCD_CDCD);
+}
+
+#[test]
+fn sram_wide_writes_store_one_byte() {
+    let mut gba = boot(ROM);
+    gba.bus.write::<u32>(0x0E00_0021, 0x4433_2211);
+    assert_eq!(gba.bus.read::<u8>(0x0E00_0020), 0);
+    assert_eq!(gba.bus.read::<u8>(0x0E00_0021), 0x22);
+    assert_eq!(gba.bus.read::<u8>(0x0E00_0022), 0);
+}
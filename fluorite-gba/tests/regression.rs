@@ -0,0 +1,255 @@
+//! Boots the test roms under `roms/` and compares the frame shown after a fixed number of
+//! frames against the golden images in `tests/golden`. Run with `UPDATE_GOLDEN=1` to
+//! replace the golden images with the current output. A markdown report of every rom,
+//! along with the frames that didn't match, is written to the target directory.
+
+use fluorite_gba::{
+    consts::{CLOCKS_PER_FRAME, HEIGHT, WIDTH},
+    gba::Gba,
+    state, AudioInterface,
+};
+use std::{
+    any::Any,
+    fmt::Write,
+    fs::{self, File},
+    io::BufWriter,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+// Path under `roms/` and the frame that is compared. The rtc demo is left out, it shows
+// the time of the host. Some roms share a golden image on purpose: they differ in code
+// that doesn't change the compared frame, so they still catch crashes on their own.
+const ROMS: &[(&str, usize)] = &[
+    ("arm_wrestler/armwrestler.gba", 120),
+    ("cpu_test/CPUTest.gba", 120),
+    ("fuzz/ARM_Any.gba", 120),
+    ("fuzz/ARM_DataProcessing.gba", 120),
+    ("fuzz/FuzzARM.gba", 120),
+    ("fuzz/THUMB_Any.gba", 120),
+    ("fuzz/THUMB_DataProcessing.gba", 120),
+    ("belogic_sound_tests/directsoundrom.gba", 60),
+    // The sound tests below only differ in audio and all show the same menu
+    ("belogic_sound_tests/sound3demorom.gba", 60),
+    ("belogic_sound_tests/soundtest1.gba", 60),
+    ("belogic_sound_tests/soundtest2.gba", 60),
+    ("belogic_sound_tests/soundtest4.gba", 60),
+    ("tonc/bigmap.gba", 120),
+    ("tonc/bld_demo.gba", 120),
+    ("tonc/bm_modes.gba", 120),
+    ("tonc/brin_demo.gba", 120),
+    ("tonc/cbb_demo.gba", 120),
+    ("tonc/dma_demo.gba", 120),
+    ("tonc/first.gba", 120),
+    ("tonc/hello.gba", 120),
+    ("tonc/irq_demo.gba", 120),
+    ("tonc/key_demo.gba", 120),
+    ("tonc/m3_demo.gba", 120),
+    ("tonc/m7_demo.gba", 120),
+    // The multiboot build of m7_demo, which runs from EWRAM instead of the rom
+    ("tonc/m7_demo_mb.gba", 120),
+    ("tonc/m7_ex.gba", 120),
+    ("tonc/mos_demo.gba", 120),
+    ("tonc/oacombo.gba", 120),
+    ("tonc/obj_aff.gba", 120),
+    ("tonc/obj_demo.gba", 120),
+    ("tonc/octtest.gba", 120),
+    ("tonc/pageflip.gba", 120),
+    ("tonc/prio_demo.gba", 120),
+    ("tonc/sbb_aff.gba", 120),
+    ("tonc/sbb_reg.gba", 120),
+    // Draws the same picture as first, with register macros instead of raw addresses
+    ("tonc/second.gba", 120),
+    ("tonc/snd1_demo.gba", 120),
+    ("tonc/swi_demo.gba", 120),
+    ("tonc/swi_vsync.gba", 120),
+    ("tonc/tmr_demo.gba", 120),
+    ("tonc/tte_demo.gba", 120),
+    ("tonc/txt_bm.gba", 120),
+    ("tonc/txt_obj.gba", 120),
+    ("tonc/txt_se1.gba", 120),
+    ("tonc/txt_se2.gba", 120),
+    ("tonc/win_demo.gba", 120),
+    // The jsmolka suite is a submodule, its roms are reported as missing until it is
+    // checked out
+    ("jsmolka/arm/arm.gba", 120),
+    ("jsmolka/thumb/thumb.gba", 120),
+    ("jsmolka/bios/bios.gba", 120),
+    ("jsmolka/memory/memory.gba", 120),
+    ("jsmolka/nes/nes.gba", 120),
+    ("jsmolka/ppu/hello.gba", 120),
+    ("jsmolka/ppu/shades.gba", 120),
+    ("jsmolka/ppu/stripes.gba", 120),
+    ("jsmolka/save/flash64.gba", 120),
+    ("jsmolka/save/flash128.gba", 120),
+    ("jsmolka/save/none.gba", 120),
+    ("jsmolka/save/sram.gba", 120),
+    ("jsmolka/unsafe/unsafe.gba", 120),
+    ("beeg.gba", 120),
+    ("enbyrights.gba", 120),
+    ("first-1.gba", 120),
+    ("irqDemo.gba", 120),
+    ("suite.gba", 120),
+    ("yoshi_dma.gba", 120),
+];
+
+enum Status {
+    Pass,
+    Mismatch,
+    NoGolden,
+    Crash(String),
+    Missing,
+}
+
+impl Status {
+    fn report(&self) -> String {
+        match self {
+            Status::Pass => "✔️".into(),
+            Status::Mismatch => "❌ mismatch".into(),
+            Status::NoGolden => "❔ no golden image".into(),
+            Status::Crash(msg) => format!("❌ crash: {}", msg.replace('|', "\\|")),
+            Status::Missing => "❔ rom missing".into(),
+        }
+    }
+}
+
+struct NoAudio;
+
+impl AudioInterface for NoAudio {
+    fn write(&mut self, _: [i16; 2]) {}
+}
+
+#[test]
+fn screenshots() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let roms = manifest.join("../roms");
+    let golden = manifest.join("tests/golden");
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("regression");
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    fs::create_dir_all(&output).unwrap();
+
+    Gba::load_audio(Box::leak(Box::new(NoAudio)));
+
+    let mut results = Vec::new();
+    for &(rom, frames) in ROMS {
+        let image = Path::new(rom).with_extension("png");
+        let status = if !roms.join(rom).exists() {
+            Status::Missing
+        } else {
+            match panic::catch_unwind(AssertUnwindSafe(|| run(&roms.join(rom), frames))) {
+                Err(payload) => Status::Crash(panic_message(payload)),
+                Ok(pixels) if update => {
+                    save_png(&golden.join(&image), &pixels);
+                    Status::Pass
+                }
+                Ok(pixels) => match load_png(&golden.join(&image)) {
+                    Some(expected) if hash(&expected) == hash(&pixels) => Status::Pass,
+                    expected => {
+                        save_png(&output.join(&image), &pixels);
+                        if expected.is_some() {
+                            Status::Mismatch
+                        } else {
+                            Status::NoGolden
+                        }
+                    }
+                },
+            }
+        };
+        results.push((rom, status));
+    }
+
+    let report = output.join("report.md");
+    fs::write(&report, write_report(&results)).unwrap();
+    println!("Report written to {}", report.display());
+
+    // Only roms that matched before can regress, the others are known to be broken
+    let failed: Vec<_> = results
+        .iter()
+        .filter(|(rom, status)| match status {
+            Status::Mismatch => true,
+            Status::Crash(_) => golden.join(Path::new(rom).with_extension("png")).exists(),
+            _ => false,
+        })
+        .map(|(rom, _)| *rom)
+        .collect();
+    assert!(failed.is_empty(), "Regressions in {failed:?}");
+}
+
+fn run(rom: &Path, frames: usize) -> Vec<u16> {
+    let mut gba = Gba::new(None).unwrap();
+    // Never write a backup next to the rom
+    gba.set_persist(false);
+    gba.load_rom(rom);
+    gba.reset();
+    for _ in 0..frames {
+        gba.run(CLOCKS_PER_FRAME);
+    }
+    gba.get_pixels().iter().map(|p| p & 0x7FFF).collect()
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".into()
+    }
+}
+
+fn hash(pixels: &[u16]) -> u64 {
+    let bytes: Vec<u8> = pixels.iter().flat_map(|p| p.to_le_bytes()).collect();
+    state::hash(&bytes)
+}
+
+fn load_png(path: &Path) -> Option<Vec<u16>> {
+    let decoder = png::Decoder::new(File::open(path).ok()?);
+    let mut reader = decoder.read_info().unwrap();
+    let mut data = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut data).unwrap();
+
+    let pixels = data
+        .chunks(3)
+        .map(|c| (c[0] >> 3) as u16 | ((c[1] >> 3) as u16) << 5 | ((c[2] >> 3) as u16) << 10)
+        .collect();
+    Some(pixels)
+}
+
+fn save_png(path: &Path, pixels: &[u16]) {
+    // Expand the 5 bit channels to the full 8 bit range, `load_png` drops the low bits again
+    let expand = |c: u16| ((c << 3) | (c >> 2)) as u8;
+    let data: Vec<u8> = pixels
+        .iter()
+        .flat_map(|&p| [p & 0x1F, p >> 5 & 0x1F, p >> 10 & 0x1F].map(expand))
+        .collect();
+
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let file = BufWriter::new(File::create(path).unwrap());
+    let mut encoder = png::Encoder::new(file, WIDTH as u32, HEIGHT as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(&data)
+        .unwrap();
+}
+
+fn write_report(results: &[(&str, Status)]) -> String {
+    let mut report = String::from("# Regression report\n");
+    let mut section = None;
+    for (rom, status) in results {
+        let path = PathBuf::from(rom);
+        let dir = match path.parent() {
+            Some(dir) if dir != Path::new("") => dir.display().to_string(),
+            _ => "misc".into(),
+        };
+        if section.as_ref() != Some(&dir) {
+            write!(report, "\n{dir}\n\n| name | passes |\n|------|--------|\n").unwrap();
+            section = Some(dir);
+        }
+        let name = path.file_stem().unwrap().to_string_lossy();
+        writeln!(report, "| {name} | {} |", status.report()).unwrap();
+    }
+    report
+}
@@ -0,0 +1,128 @@
+//! Save states restore the exact emulation and reject states they can't restore
+
+mod common;
+
+use common::{boot, frame_hash, init_audio, rom, run_frames};
+use fluorite_gba::{
+    gba::Gba,
+    io::scheduler::EventType,
+    state::{SaveState, StateError, StateReader, StateWriter},
+};
+
+// Animates every frame, so a state that restores the wrong frame shows
+const ROM: &str = "tonc/m7_demo.gba";
+
+// The header is 36 bytes, the payload starts right after it
+const PAYLOAD: usize = 36;
+
+#[test]
+fn round_trip() {
+    let mut gba = boot(ROM);
+    run_frames(&mut gba, 30);
+    let state = gba.save_state();
+    run_frames(&mut gba, 30);
+    let expected = frame_hash(&gba);
+
+    let mut other = boot(ROM);
+    other.load_state(&state).unwrap();
+    run_frames(&mut other, 30);
+    assert_eq!(frame_hash(&other), expected);
+
+    // Loading into the emulator that saved it rewinds it
+    gba.load_state(&state).unwrap();
+    run_frames(&mut gba, 30);
+    assert_eq!(frame_hash(&gba), expected);
+}
+
+#[test]
+fn wrong_rom() {
+    let state = boot(ROM).save_state();
+    let mut gba = boot("tonc/first.gba");
+    assert_eq!(gba.load_state(&state), Err(StateError::RomMismatch));
+}
+
+#[test]
+fn wrong_bios() {
+    let state = boot(ROM).save_state();
+
+    init_audio();
+    let bios = std::fs::read(rom("gba_bios.bin")).unwrap();
+    let mut gba = Gba::new(Some(bios)).unwrap();
+    gba.set_persist(false);
+    gba.load_rom(rom(ROM));
+    gba.reset();
+    assert_eq!(gba.load_state(&state), Err(StateError::BiosMismatch));
+}
+
+#[test]
+fn corrupt_payload() {
+    let mut state = boot(ROM).save_state();
+    state[PAYLOAD + 100] ^= 1;
+    let mut gba = boot(ROM);
+    assert_eq!(gba.load_state(&state), Err(StateError::Corrupt));
+}
+
+#[test]
+fn truncated() {
+    let state = boot(ROM).save_state();
+    let mut gba = boot(ROM);
+    assert_eq!(
+        gba.load_state(&state[..state.len() - 1]),
+        Err(StateError::Corrupt)
+    );
+    assert_eq!(
+        gba.load_state(&state[..PAYLOAD - 1]),
+        Err(StateError::InvalidHeader)
+    );
+}
+
+#[test]
+fn rejected_state_leaves_emulator_untouched() {
+    let mut gba = boot(ROM);
+    run_frames(&mut gba, 10);
+    let before = gba.save_state();
+
+    // A payload with a valid header that runs out halfway through the bus
+    let mut short = boot(ROM).save_state();
+    short.truncate(PAYLOAD + 1000);
+    let len = (short.len() - PAYLOAD) as u64;
+    short[20..28].copy_from_slice(&len.to_le_bytes());
+    let hash = fluorite_gba::state::hash(&short[PAYLOAD..]);
+    short[28..36].copy_from_slice(&hash.to_le_bytes());
+
+    assert_eq!(gba.load_state(&short), Err(StateError::Corrupt));
+    assert_eq!(gba.save_state(), before);
+}
+
+/// Writes `value` into a state with a valid header and loads it into `into`
+fn reload<T: SaveState, U: SaveState>(value: T, into: &mut U) -> Result<(), StateError> {
+    let mut w = StateWriter::new();
+    value.save_state(&mut w);
+    let state = w.finish(0, true);
+    let mut r = StateReader::new(&state, 0, true)?;
+    into.load_state(&mut r);
+    r.finish()
+}
+
+#[test]
+fn event_index_out_of_range() {
+    let mut event = EventType::SerialPoll;
+    assert_eq!(reload(EventType::TimerOverflow(3), &mut event), Ok(()));
+    assert_eq!(event, EventType::TimerOverflow(3));
+    assert_eq!(reload(EventType::FrameSequencer(7), &mut event), Ok(()));
+
+    for invalid in [EventType::TimerOverflow(4), EventType::FrameSequencer(8)] {
+        assert_eq!(reload(invalid, &mut event), Err(StateError::Corrupt));
+    }
+}
+
+#[test]
+fn length_past_the_payload() {
+    // Fails before allocating the elements
+    let mut vec = Vec::<u32>::new();
+    assert_eq!(reload(usize::MAX, &mut vec), Err(StateError::Corrupt));
+    assert!(vec.is_empty());
+
+    let mut deque = std::collections::VecDeque::<u32>::new();
+    assert_eq!(reload(usize::MAX, &mut deque), Err(StateError::Corrupt));
+}
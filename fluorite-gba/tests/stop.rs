@@ -0,0 +1,63 @@
+//! Stop mode turns off the system clock, so timers and sound pause until a keypad, serial
+//! or gamepak interrupt wakes the CPU.
+
+mod common;
+
+use common::{boot, run_frames};
+use fluorite_gba::{gba::Gba, io::keypad::KeyState};
+
+// Doesn't enable interrupts, so IF is left alone
+const ROM: &str = "tonc/first.gba";
+
+const TM0CNT_L: u32 = 0x0400_0100;
+const TM0CNT_H: u32 = 0x0400_0102;
+const KEYCNT: u32 = 0x0400_0132;
+const IE: u32 = 0x0400_0200;
+const IF: u32 = 0x0400_0202;
+const HALTCNT: u32 = 0x0400_0301;
+
+const TIMER0: u16 = 1 << 3;
+const KEYPAD: u16 = 1 << 12;
+
+/// Starts timer 0 with its interrupt enabled, overflowing every 256 cycles
+fn start_timer(gba: &mut Gba) {
+    gba.bus.write::<u16>(IE, TIMER0 | KEYPAD);
+    gba.bus.write::<u16>(TM0CNT_L, 0xFF00);
+    gba.bus.write::<u16>(TM0CNT_H, 0x00C0);
+    gba.bus.write::<u16>(IF, TIMER0);
+}
+
+#[test]
+fn timers_pause_in_stop_mode() {
+    let mut gba = boot(ROM);
+    gba.bus.write::<u16>(KEYCNT, 0x4000 | KeyState::A);
+    start_timer(&mut gba);
+    // The timer starts a cycle after it is enabled
+    for _ in 0..4 {
+        gba.cpu.emulate_instr(&mut gba.bus);
+    }
+    let counter = gba.bus.read::<u16>(TM0CNT_L);
+    gba.bus.write::<u8>(HALTCNT, 0x80);
+
+    run_frames(&mut gba, 3);
+    assert!(gba.bus.is_halted());
+    assert_eq!(gba.bus.read::<u16>(IF) & TIMER0, 0);
+    assert_eq!(gba.bus.read::<u16>(TM0CNT_L), counter);
+
+    // The keypad interrupt ends stop mode and the timer picks up where it left off
+    gba.press(KeyState::A);
+    run_frames(&mut gba, 1);
+    assert!(!gba.bus.is_halted());
+    assert_ne!(gba.bus.read::<u16>(IF) & TIMER0, 0);
+}
+
+#[test]
+fn timers_wake_from_halt() {
+    let mut gba = boot(ROM);
+    start_timer(&mut gba);
+    gba.bus.write::<u8>(HALTCNT, 0);
+
+    run_frames(&mut gba, 1);
+    assert!(!gba.bus.is_halted());
+    assert_ne!(gba.bus.read::<u16>(IF) & TIMER0, 0);
+}
@@ -0,0 +1,55 @@
+//! Runs undefined and coprocessor instructions from IWRAM and checks that the CPU enters
+//! the undefined instruction vector.
+
+mod common;
+
+use common::boot;
+use fluorite_gba::{
+    arm::registers::{Mode, Reg},
+    gba::Gba,
+};
+
+const ROM: &str = "tonc/first.gba";
+const CODE: u32 = 0x0300_0000;
+
+/// Executes the instruction at `CODE` and checks the exception entry, `size` is the
+/// length of the instruction
+fn check_exception(gba: &mut Gba, size: u32) {
+    gba.cpu.regs.set_c(true);
+    gba.cpu.regs.set_i(false);
+    let cpsr = gba.cpu.regs.get_status().raw();
+    gba.cpu.set_next_pc(&gba.bus, CODE);
+    gba.cpu.emulate_instr(&mut gba.bus);
+
+    let regs = &gba.cpu.regs;
+    assert_eq!(regs.get_mode(), Mode::Undefined);
+    assert_eq!(regs.get_reg(Reg::R14), CODE + size);
+    assert_eq!(regs.get_banked(Mode::Undefined, Reg::Spsr), cpsr);
+    assert!(!regs.get_t());
+    assert!(regs.get_i());
+    assert!(regs.get_c());
+    assert_eq!(gba.cpu.next_pc(), 0x04);
+}
+
+#[test]
+fn undefined_arm() {
+    let mut gba = boot(ROM);
+    gba.bus.write::<u32>(CODE, 0xE600_0010);
+    check_exception(&mut gba, 4);
+}
+
+#[test]
+fn undefined_thumb() {
+    let mut gba = boot(ROM);
+    gba.bus.write::<u16>(CODE, 0xDE00);
+    gba.cpu.regs.set_t(true);
+    check_exception(&mut gba, 2);
+}
+
+#[test]
+fn coprocessor() {
+    // mcr p15, 0, r0, c1, c0, 0
+    let mut gba = boot(ROM);
+    gba.bus.write::<u32>(CODE, 0xEE01_0F10);
+    check_exception(&mut gba, 4);
+}
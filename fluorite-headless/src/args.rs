@@ -0,0 +1,180 @@
+use fluorite_gba::io::{keypad::KeyState, sio::LinkAddr};
+use std::{ops::Range, path::PathBuf};
+
+pub const USAGE: &str = "\
+usage: fluorite-headless <rom> [options]
+
+  --frames <n>              frames to run for, 3600 by default
+  --bios <path>             BIOS dump, the built-in replacement is used otherwise
+  --boot-bios               show the boot logo before starting the rom, needs --bios
+  --press <frame>:<keys>    press keys before running a frame, eg. 60:A,START
+  --release <frame>:<keys>  release keys before running a frame
+  --until-log <text>        stop once an mGBA debug message contains text
+  --until-hash <hash>       stop once the frame hashes to hash
+  --screenshot <path>       save the last frame as png
+  --log <path>              write mGBA debug messages to path instead of stdout
+  --gdb <port>              wait for GDB to connect to port on localhost before starting
+  --link-host <addr>        wait for other emulators to join a link cable on addr, which
+                            is ip:port or unix:<path>
+  --link-players <n>        players on the hosted link cable including this one, 2 by default
+  --link-join <addr>        join the link cable of another emulator before starting
+  --trace <path>            write the registers and disassembly of every instruction to path
+  --trace-range <from>-<to> only trace instructions in an address range, eg. 8000000-8000400
+
+Exits with 0 once the frames ran or a --until condition is met, 1 if a condition
+wasn't met in time or the rom logged a fatal error and 2 on invalid arguments.";
+
+pub struct Args {
+    pub rom: PathBuf,
+    pub bios: Option<PathBuf>,
+    pub boot_bios: bool,
+    pub frames: usize,
+    pub input: Vec<Input>,
+    pub until: Vec<Condition>,
+    pub screenshot: Option<PathBuf>,
+    pub log: Option<PathBuf>,
+    pub gdb: Option<u16>,
+    pub link: Option<Link>,
+    pub trace: Option<PathBuf>,
+    pub trace_range: Option<Range<u32>>,
+}
+
+pub struct Input {
+    pub frame: usize,
+    pub keys: u16,
+    pub pressed: bool,
+}
+
+pub enum Link {
+    Host(LinkAddr, usize),
+    Join(LinkAddr),
+}
+
+pub enum Condition {
+    Log(String),
+    Hash(u64),
+}
+
+impl Args {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut rom = None;
+        let mut link_players = None;
+        let mut parsed = Self {
+            rom: PathBuf::new(),
+            bios: None,
+            boot_bios: false,
+            frames: 3600,
+            input: Vec::new(),
+            until: Vec::new(),
+            screenshot: None,
+            log: None,
+            gdb: None,
+            link: None,
+            trace: None,
+            trace_range: None,
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+            match arg.as_str() {
+                "--frames" => {
+                    let frames = value()?;
+                    parsed.frames = frames
+                        .parse()
+                        .map_err(|_| format!("invalid frame count {frames}"))?;
+                }
+                "--bios" => parsed.bios = Some(value()?.into()),
+                "--boot-bios" => parsed.boot_bios = true,
+                "--press" => parsed.input.push(Input::parse(&value()?, true)?),
+                "--release" => parsed.input.push(Input::parse(&value()?, false)?),
+                "--until-log" => parsed.until.push(Condition::Log(value()?)),
+                "--until-hash" => {
+                    let hash = value()?;
+                    let hash = u64::from_str_radix(hash.trim_start_matches("0x"), 16)
+                        .map_err(|_| format!("invalid hash {hash}"))?;
+                    parsed.until.push(Condition::Hash(hash));
+                }
+                "--screenshot" => parsed.screenshot = Some(value()?.into()),
+                "--log" => parsed.log = Some(value()?.into()),
+                "--gdb" => {
+                    let port = value()?;
+                    parsed.gdb = Some(port.parse().map_err(|_| format!("invalid port {port}"))?);
+                }
+                // Parsing a link address can't fail
+                "--link-host" => parsed.link = Some(Link::Host(value()?.parse().unwrap(), 2)),
+                "--link-players" => {
+                    let players = value()?;
+                    let players = players
+                        .parse()
+                        .ok()
+                        .filter(|n| (2..=4).contains(n))
+                        .ok_or(format!("invalid player count {players}, expected 2 to 4"))?;
+                    link_players = Some(players);
+                }
+                "--link-join" => parsed.link = Some(Link::Join(value()?.parse().unwrap())),
+                "--trace" => parsed.trace = Some(value()?.into()),
+                "--trace-range" => {
+                    let range = value()?;
+                    let invalid = || format!("invalid address range {range}");
+                    let parse = |addr: &str| {
+                        u32::from_str_radix(addr.trim_start_matches("0x"), 16)
+                            .map_err(|_| invalid())
+                    };
+                    let (from, to) = range.split_once('-').ok_or_else(invalid)?;
+                    parsed.trace_range = Some(parse(from)?..parse(to)?);
+                }
+                _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+                _ if rom.is_none() => rom = Some(arg.into()),
+                _ => return Err(format!("unexpected argument {arg}")),
+            }
+        }
+
+        parsed.rom = rom.ok_or("no rom given")?;
+        if parsed.boot_bios && parsed.bios.is_none() {
+            return Err("--boot-bios needs a BIOS dump".into());
+        }
+        match (&mut parsed.link, link_players) {
+            (Some(Link::Host(_, players)), Some(n)) => *players = n,
+            (_, Some(_)) => return Err("--link-players needs --link-host".into()),
+            _ => (),
+        }
+        if parsed.trace_range.is_some() && parsed.trace.is_none() {
+            return Err("--trace-range needs --trace".into());
+        }
+        Ok(parsed)
+    }
+}
+
+impl Input {
+    fn parse(arg: &str, pressed: bool) -> Result<Self, String> {
+        let (frame, names) = arg
+            .split_once(':')
+            .ok_or(format!("expected <frame>:<keys>, got {arg}"))?;
+        let frame = frame
+            .parse()
+            .map_err(|_| format!("invalid frame {frame}"))?;
+
+        let mut keys = 0;
+        for name in names.split(',') {
+            keys |= match name.to_ascii_uppercase().as_str() {
+                "A" => KeyState::A,
+                "B" => KeyState::B,
+                "SELECT" => KeyState::SELECT,
+                "START" => KeyState::START,
+                "RIGHT" => KeyState::RIGHT,
+                "LEFT" => KeyState::LEFT,
+                "UP" => KeyState::UP,
+                "DOWN" => KeyState::DOWN,
+                "R" => KeyState::R,
+                "L" => KeyState::L,
+                _ => return Err(format!("unknown key {name}")),
+            };
+        }
+
+        Ok(Self {
+            frame,
+            keys,
+            pressed,
+        })
+    }
+}
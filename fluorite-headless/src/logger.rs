@@ -0,0 +1,24 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Prints the warnings and errors of the core to stderr, so they don't mix with the
+/// messages of the rom
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+pub fn install() {
+    log::set_logger(&StderrLogger).expect("Logger already installed");
+    log::set_max_level(LevelFilter::Warn);
+}
@@ -0,0 +1,190 @@
+use args::{Args, Condition, Link, USAGE};
+use fluorite_common::flume;
+use fluorite_gba::{
+    arm::trace::TraceFilter,
+    consts::{CLOCKS_PER_FRAME, HEIGHT, WIDTH},
+    gba::Gba,
+    gdb::GdbStub,
+    io::{mgba_debug::DebugLevel, sio::SocketLink},
+    state, AudioInterface,
+};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    process::{exit, ExitCode},
+};
+
+mod args;
+mod logger;
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+struct NoAudio;
+
+impl AudioInterface for NoAudio {
+    fn write(&mut self, _: [i16; 2]) {}
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{e}\n\n{USAGE}");
+        exit(EXIT_USAGE)
+    });
+    logger::install();
+
+    let bios = args.bios.as_ref().map(|path| {
+        std::fs::read(path).unwrap_or_else(|e| {
+            eprintln!("Failed to read BIOS {}: {e}", path.display());
+            exit(EXIT_USAGE)
+        })
+    });
+    let mut gba = Gba::new(bios).unwrap_or_else(|e| {
+        eprintln!("Invalid BIOS: {e}");
+        exit(EXIT_USAGE)
+    });
+    if let Err(e) = std::fs::metadata(&args.rom) {
+        eprintln!("Failed to read rom {}: {e}", args.rom.display());
+        exit(EXIT_USAGE)
+    }
+    Gba::load_audio(Box::leak(Box::new(NoAudio)));
+    gba.set_skip_bios(!args.boot_bios);
+    // Test runs never touch the save file of the rom
+    gba.set_persist(false);
+    gba.load_rom(&args.rom);
+    gba.reset();
+
+    let (tx, messages) = flume::unbounded();
+    gba.set_debug_sink(move |level, message| {
+        let _ = tx.send((level, message.to_string()));
+    });
+
+    if let Some(path) = &args.trace {
+        let mut trace = BufWriter::new(File::create(path).unwrap_or_else(|e| {
+            eprintln!("Failed to create {}: {e}", path.display());
+            exit(EXIT_USAGE)
+        }));
+        let filter = TraceFilter {
+            range: args.trace_range.clone(),
+            ..Default::default()
+        };
+        gba.trace(filter, move |entry| {
+            writeln!(trace, "{entry}").expect("Failed to write trace")
+        });
+    }
+
+    if let Some(link) = &args.link {
+        let link = match link {
+            Link::Host(addr, players) => {
+                eprintln!("Waiting for {players} players on {addr:?}");
+                SocketLink::host(addr, *players)
+            }
+            Link::Join(addr) => SocketLink::join(addr),
+        };
+        let link = link.unwrap_or_else(|e| {
+            eprintln!("Failed to connect the link cable: {e}");
+            exit(EXIT_FAILURE)
+        });
+        gba.set_link(Box::new(link));
+    }
+
+    let mut gdb = args.gdb.map(|port| {
+        let mut gdb = GdbStub::bind(port).unwrap_or_else(|e| {
+            eprintln!("Failed to listen for GDB on port {port}: {e}");
+            exit(EXIT_USAGE)
+        });
+        eprintln!("Waiting for GDB on port {port}");
+        gdb.wait_for_debugger().unwrap_or_else(|e| {
+            eprintln!("Failed to attach GDB: {e}");
+            exit(EXIT_FAILURE)
+        });
+        gdb
+    });
+
+    let mut log: Box<dyn Write> = match &args.log {
+        Some(path) => Box::new(BufWriter::new(File::create(path).unwrap_or_else(|e| {
+            eprintln!("Failed to create {}: {e}", path.display());
+            exit(EXIT_USAGE)
+        }))),
+        None => Box::new(io::stdout()),
+    };
+
+    let mut frames = 0;
+    let mut done = false;
+    let mut fatal = false;
+    let until_hash = args
+        .until
+        .iter()
+        .any(|until| matches!(until, Condition::Hash(_)));
+    while frames < args.frames && !done && !fatal {
+        for input in args.input.iter().filter(|input| input.frame == frames) {
+            if input.pressed {
+                gba.press(input.keys);
+            } else {
+                gba.release(input.keys);
+            }
+        }
+        match &mut gdb {
+            // Frames only end while GDB lets the rom run
+            Some(gdb) => while !gdb.run(&mut gba, CLOCKS_PER_FRAME) {},
+            None => {
+                gba.run(CLOCKS_PER_FRAME);
+            }
+        }
+        frames += 1;
+
+        for (level, message) in messages.try_iter() {
+            writeln!(log, "[{level}] {message}").expect("Failed to write log");
+            fatal |= level == DebugLevel::Fatal;
+            done |= args.until.iter().any(
+                |until| matches!(until, Condition::Log(text) if message.contains(text.as_str())),
+            );
+        }
+        if until_hash {
+            let hash = frame_hash(gba.get_pixels());
+            done |= args
+                .until
+                .iter()
+                .any(|until| matches!(until, Condition::Hash(h) if *h == hash));
+        }
+    }
+    log.flush().expect("Failed to write log");
+    // Drops the trace file, which flushes it
+    gba.stop_trace();
+
+    println!("frames: {frames}");
+    println!("hash: {:016X}", frame_hash(gba.get_pixels()));
+
+    if let Some(path) = &args.screenshot {
+        if let Err(e) = save_png(path, gba.get_pixels()) {
+            eprintln!("Failed to save {}: {e}", path.display());
+        }
+    }
+
+    if fatal || !(done || args.until.is_empty()) {
+        ExitCode::from(EXIT_FAILURE as u8)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn frame_hash(pixels: &[u16]) -> u64 {
+    let bytes: Vec<u8> = pixels.iter().flat_map(|p| p.to_le_bytes()).collect();
+    state::hash(&bytes)
+}
+
+fn save_png(path: &Path, pixels: &[u16]) -> Result<(), png::EncodingError> {
+    // Expand the 5 bit channels to the full 8 bit range
+    let expand = |c: u16| ((c << 3) | (c >> 2)) as u8;
+    let data: Vec<u8> = pixels
+        .iter()
+        .flat_map(|&p| [p & 0x1F, p >> 5 & 0x1F, p >> 10 & 0x1F].map(expand))
+        .collect();
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, WIDTH as u32, HEIGHT as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&data)
+}